@@ -98,7 +98,10 @@ impl<'a> App<'a> {
     fn update(&mut self) {
         self.timer.update();
         self.ui.update(self.timer.delta_time(), self.window, &mut self.scene, &mut self.renderer);
-        self.scene.update(&self.timer, &self.input_manager);
+        // input is ignored while recording so the frames only depend on the scene
+        if self.renderer.recording.is_none() {
+            self.scene.update(&self.timer, &self.input_manager);
+        }
     }
 
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -107,7 +110,7 @@ impl<'a> App<'a> {
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         let future = async move {
-            self.renderer.render(&mut self.ui, &mut self.scene, self.timer.delta_time().as_secs_f32()).await
+            self.renderer.render(&mut self.ui, &mut self.scene).await
         };
 
         pollster::block_on(future)
@@ -117,7 +120,11 @@ impl<'a> App<'a> {
         self.update();
 
         match self.render() {
-            Ok(_) => {},
+            // step the scene after each recorded frame so the first is at time 0
+            Ok(_) => if let Some(recording) = &self.renderer.recording {
+                self.scene.advance(recording.dt());
+                self.window.request_redraw();
+            },
             Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) =>
                 self.resize(self.window.inner_size()),
             Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
@@ -0,0 +1,194 @@
+use std::{fmt::Display, fs::File, io::BufWriter, ops::Range, path::{Path, PathBuf}, process::exit};
+
+use ray_tracer::{renderer::{core::{RenderCore, DEFAULT_RESOLUTION}, recording::{frame_path, RecordingFormat, Y4mWriter}}, scene::{scene_file::SceneFile, Scene}};
+
+const USAGE: &str = "\
+usage: bhusie-render [options]
+
+options:
+    --scene FILE        ron scene saved from the viewer, defaults to the
+                        startup scene
+    --resolution WxH    output size, defaults to 1918x1081
+    --frames A..B       frames A up to but not including B, defaults to 0..1.
+                        The scene is still stepped through the frames
+                        before A so they match a full recording
+    --dt SECONDS        simulated time between frames, above 0, defaults
+                        to 1/30
+    --fps N             same as --dt 1/N
+    --output PATTERN    image path, a run of # is replaced by the zero padded
+                        frame number, defaults to frame_####.png. A .y4m
+                        path writes every frame to one uncompressed stream
+    --help              print this";
+
+struct Args {
+    scene: Option<PathBuf>,
+    resolution: (u32, u32),
+    frames: Range<u32>,
+    dt: f32,
+    output: String,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            scene: None,
+            resolution: DEFAULT_RESOLUTION,
+            frames: 0..1,
+            dt: 1.0 / 30.0,
+            output: "frame_####.png".into(),
+        };
+
+        let mut iter = std::env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            if arg == "--help" {
+                println!("{}", USAGE);
+                exit(0);
+            }
+
+            let value = iter.next().ok_or(format!("missing value for {}", arg))?;
+
+            match arg.as_str() {
+                "--scene" => args.scene = Some(value.into()),
+                "--resolution" => args.resolution = parse_resolution(&value)?,
+                "--frames" => args.frames = parse_frames(&value)?,
+                "--dt" => args.dt = value.parse::<f32>().ok().filter(|dt| dt.is_finite() && *dt > 0.0).ok_or(format!("invalid dt {}", value))?,
+                "--fps" => args.dt = 1.0 / value.parse::<u32>().ok().filter(|fps| *fps > 0).ok_or(format!("invalid fps {}", value))? as f32,
+                "--output" => args.output = value,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        let format = RecordingFormat::from_path(&args.output);
+
+        if format == RecordingFormat::PngSequence && args.frames.len() > 1 && !args.output.contains('#') {
+            return Err("output needs a # to number more than one frame".into());
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let error = || format!("invalid resolution {}, expected WxH", value);
+    let (width, height) = value.split_once('x').ok_or_else(error)?;
+
+    let width: u32 = width.parse().map_err(|_| error())?;
+    let height: u32 = height.parse().map_err(|_| error())?;
+
+    if width == 0 || height == 0 {
+        return Err(error());
+    }
+
+    Ok((width, height))
+}
+
+fn parse_frames(value: &str) -> Result<Range<u32>, String> {
+    let error = || format!("invalid frames {}, expected A..B with A < B", value);
+    let (start, end) = value.split_once("..").ok_or_else(error)?;
+
+    let start: u32 = start.parse().map_err(|_| error())?;
+    let end: u32 = end.parse().map_err(|_| error())?;
+
+    if start >= end {
+        return Err(error());
+    }
+
+    Ok(start..end)
+}
+
+async fn render(args: Args) {
+    let scene_file = args.scene.as_ref().map(|path| match SceneFile::load(path) {
+        Ok(scene_file) => scene_file,
+        Err(error) => {
+            eprintln!("failed to load {}: {}", path.display(), error);
+            exit(1);
+        }
+    });
+
+    let mut scene = Scene::new();
+    let mut core = RenderCore::headless(&scene, args.resolution).await;
+
+    if let Some(scene_file) = scene_file {
+        scene_file.apply(&mut scene, &mut core);
+    }
+
+    let mut y4m = None;
+
+    if RecordingFormat::from_path(&args.output) == RecordingFormat::Y4m {
+        if let Some(parent) = Path::new(&args.output).parent() {
+            or_exit(std::fs::create_dir_all(parent), || format!("failed to create {}", parent.display()));
+        }
+
+        let file = or_exit(File::create(&args.output), || format!("failed to create {}", args.output));
+
+        // y4m only takes a frame rate so dt is rounded to the nearest one
+        let fps = (1.0 / args.dt).round().max(1.0) as u32;
+
+        let writer = Y4mWriter::new(BufWriter::new(file), args.resolution, fps);
+        y4m = Some(or_exit(writer, || format!("failed to write {}", args.output)));
+    }
+
+    // stepped the same way as a recording in the viewer, frames before the
+    // range are simulated but not rendered so any range gives the same frames
+    scene.rewind();
+
+    for frame in 0..args.frames.end {
+        if args.frames.contains(&frame) {
+            let image = core.render(&mut scene).await;
+
+            if let Some(y4m) = &mut y4m {
+                or_exit(y4m.write_frame(&image), || format!("failed to write {}", args.output));
+
+                log::info!("Wrote frame {} to {}", frame, args.output);
+            } else {
+                let path = frame_path(&args.output, frame);
+
+                if let Some(parent) = path.parent() {
+                    or_exit(std::fs::create_dir_all(parent), || format!("failed to create {}", parent.display()));
+                }
+
+                or_exit(image.save(&path), || format!("failed to save {}", path.display()));
+
+                log::info!("Saved frame {} to {}", frame, path.display());
+            }
+        }
+
+        scene.advance(args.dt);
+    }
+
+    if let Some(mut y4m) = y4m {
+        or_exit(y4m.flush(), || format!("failed to write {}", args.output));
+    }
+}
+
+fn or_exit<T, E: Display>(result: Result<T, E>, context: impl FnOnce() -> String) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("{}: {}", context(), error);
+            exit(1);
+        }
+    }
+}
+
+fn main() {
+    // progress is logged at info, but the user's own filter wins
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+
+    env_logger::init();
+
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            exit(1);
+        }
+    };
+
+    stacker::grow(1024 * 1024 * 1024, || {
+        pollster::block_on(render(args));
+    });
+}
@@ -107,10 +107,8 @@ impl InputManager {
                     position.y,
                 );
             },
-            WindowEvent::MouseInput { state, button, .. } => {
-                if *button == MouseButton::Left {
-                    self.left_mouse_down = *state == ElementState::Pressed;
-                }
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                self.left_mouse_down = *state == ElementState::Pressed;
             },
             _ => {}
         }
@@ -48,8 +48,13 @@ where
         }
     }
 
+    pub fn clear(&mut self) {
+        self.entities = [None; MAX_ENTITIES];
+        self.size = 0;
+    }
+
     pub fn get(&self, index: usize) -> Option<&T> {
-        if index <= self.size {
+        if index >= self.size {
             return None;
         }
 
@@ -68,10 +73,23 @@ where
         self.size
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entities[..self.size].iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entities[..self.size].iter_mut().flatten()
+    }
+
     pub fn update_buffer(&mut self, queue: &wgpu::Queue, buffer: &wgpu::Buffer) {
+        self.update_buffer_with(queue, buffer, |entity| *entity);
+    }
+
+    // uploads a changed copy of each entity, leaving the entities alone
+    pub fn update_buffer_with(&mut self, queue: &wgpu::Queue, buffer: &wgpu::Buffer, f: impl Fn(&T) -> T) {
         for i in 0..self.size {
             if let Some(entity) = &self.entities[i] {
-                self.entity_uniforms[i].update(entity);
+                self.entity_uniforms[i].update(&f(entity));
             }
         }
 
@@ -0,0 +1,629 @@
+use image::{imageops, ImageBuffer, Rgba, RgbaImage};
+use wgpu::util::DeviceExt;
+
+use crate::scene::{camera::CameraUniform, deflection::{self, DEFLECTION_LUT_IMPACT_SAMPLES, DEFLECTION_LUT_RADIUS_SAMPLES}, Scene};
+
+use super::pipelines::{bloom_pipline::{BloomDirection, BloomDownPipelineDescriptor, BloomPipeline}, fxaa_pipline::{FXAADetails, FXAADetailsUniform, FXAAPipeline, FXAAPipelineDescriptor}, hdr_pipeline::{HDRPipeline, HDRPipelineDescriptor}, mix_pipeline::{MixDetails, MixPipeline, MixPipelineDescriptor}, ray_pipeline::{RayDetails, RayDetailsUniform, RayPipeline, RayPipelineDescriptor}, sky_pipeline::{SkyDetails, SkyPipeline, SkyPipelineDescriptor}};
+
+// the ray pipelines start on a coarse grid and each level refines it
+// RAY_MULTIPLIER times, reusing the corners of the level before
+const RAY_MULTIPLIER: u32 = 3;
+const RAY_LEVELS: u32 = 4;
+
+// a 72x41 grid, roughly 1080p
+pub const DEFAULT_RESOLUTION: (u32, u32) = (1918, 1081);
+
+// output size of the finest ray level for a base grid
+pub fn ray_grid_resolution(grid: (u32, u32)) -> (u32, u32) {
+    let scale = RAY_MULTIPLIER.pow(RAY_LEVELS - 1);
+
+    ((grid.0 - 1) * scale + 1, (grid.1 - 1) * scale + 1)
+}
+
+// smallest base grid whose output covers resolution
+pub fn ray_grid_for(resolution: (u32, u32)) -> (u32, u32) {
+    let scale = RAY_MULTIPLIER.pow(RAY_LEVELS - 1);
+
+    (
+        resolution.0.saturating_sub(1).div_ceil(scale).max(1) + 1,
+        resolution.1.saturating_sub(1).div_ceil(scale).max(1) + 1,
+    )
+}
+
+// everything up to and including fxaa, rendered into an offscreen texture.
+// It only needs a device and queue so it runs without a window, Renderer
+// puts it on screen
+pub struct RenderCore {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    resolution: (u32, u32),
+
+    bloom_pipelines: Vec<BloomPipeline>,
+    sky_pipeline: SkyPipeline,
+    sky_details_buffer: wgpu::Buffer,
+    hdr_pipeline: HDRPipeline,
+
+    pub step_mode: bool,
+    pub step: bool,
+
+    pub fxaa_details: FXAADetails,
+    pub fxaa_details_uniform: FXAADetailsUniform,
+    fxaa_details_buffer: wgpu::Buffer,
+    fxaa_pipeline: FXAAPipeline,
+
+    pub mix_details: MixDetails,
+    mix_details_buffer: wgpu::Buffer,
+    mix_pipeline: MixPipeline,
+
+    pub ray_details: RayDetails,
+    ray_details_uniform: RayDetailsUniform,
+    ray_details_buffer: wgpu::Buffer,
+    ray_pipelines: Vec<RayPipeline>,
+
+    deflection_texture: wgpu::Texture,
+    deflection_lut_written: bool,
+
+    black_hole_buffer: wgpu::Buffer,
+    wormhole_buffer: wgpu::Buffer,
+
+    camera_uniform: CameraUniform,
+    camera_buffer: wgpu::Buffer,
+
+    material_buffer: wgpu::Buffer,
+    model_buffer: wgpu::Buffer,
+}
+
+impl RenderCore {
+    pub async fn request_device(instance: &wgpu::Instance, compatible_surface: Option<&wgpu::Surface<'_>>) -> (wgpu::Adapter, wgpu::Device, wgpu::Queue) {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface,
+            })
+            .await
+            .expect("Failed to find an appropriate adapter");
+
+        log::info!("Using adapter: {:?}", adapter.get_info());
+
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: adapter.features() & wgpu::Features::BGRA8UNORM_STORAGE,
+                required_limits: wgpu::Limits {
+                    ..wgpu::Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits())
+                }
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device");
+
+        (adapter, device, queue)
+    }
+
+    pub async fn headless(scene: &Scene, resolution: (u32, u32)) -> Self {
+        let instance = wgpu::Instance::default();
+        let (_, device, queue) = Self::request_device(&instance, None).await;
+
+        Self::new(device, queue, scene, resolution)
+    }
+
+    // resolution is the size of read_image, the texture itself is the
+    // nearest ray grid that covers it
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, scene: &Scene, resolution: (u32, u32)) -> Self {
+        let resolution = (resolution.0.max(1), resolution.1.max(1));
+        let material_buffer = scene.materials.create_buffer(&device);
+        let model_buffer = scene.models.create_buffer(&device);
+
+        let ray_details = RayDetails::default();
+
+        let ray_details_uniform = RayDetailsUniform::default();
+
+        let ray_details_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ray Details Buffer"),
+                contents: bytemuck::cast_slice(&[ray_details_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let camera_uniform = CameraUniform::new();
+
+        let camera_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let black_hole_buffer = scene.black_holes.create_buffer(&device);
+        let wormhole_buffer = scene.wormholes.create_buffer(&device);
+
+        let base_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: Default::default(),
+        });
+
+        let base_texture_view = base_texture.create_view(&Default::default());
+
+        let deflection_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: DEFLECTION_LUT_IMPACT_SAMPLES,
+                height: DEFLECTION_LUT_RADIUS_SAMPLES,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: Default::default(),
+        });
+
+        let deflection_view = deflection_texture.create_view(&Default::default());
+
+        let mut ray_pipelines: Vec<RayPipeline> = Vec::new();
+
+        let ray_grid = ray_grid_for(resolution);
+
+        let mut current_res = (ray_grid.0 as f32, ray_grid.1 as f32);
+        let ray_multiplier = RAY_MULTIPLIER as f32;
+        let iters = RAY_LEVELS;
+
+        for i in 0..iters {
+            log::info!("Loading ray pipeline ({}): {}, {}", i, current_res.0 as u32, current_res.1 as u32);
+
+            let prev_texture_view = match ray_pipelines.last() {
+                Some(ray_pipeline) => ray_pipeline.output_view(),
+                None => &base_texture_view
+            };
+
+            let prev_order_view = match ray_pipelines.last() {
+                Some(ray_pipeline) => ray_pipeline.order_view(),
+                None => &base_texture_view
+            };
+
+            let ray_pipeline = RayPipeline::new(RayPipelineDescriptor {
+                device: &device, 
+                queue: &queue,
+                resolution: (current_res.0 as u32, current_res.1 as u32),
+                camera_buffer: &camera_buffer,
+                black_hole_buffer: &black_hole_buffer,
+                wormhole_buffer: &wormhole_buffer,
+                material_buffer: &material_buffer,
+                model_buffer: &model_buffer,
+                ray_details_buffer: &ray_details_buffer,
+                prev_texture_view,
+                prev_order_view,
+                deflection_view: &deflection_view,
+                metric: ray_details.metric,
+            });
+
+            ray_pipelines.push(ray_pipeline);
+
+            if i < iters-1 {
+                current_res.0 = current_res.0 * ray_multiplier - (ray_multiplier - 1.0);
+                current_res.1 = current_res.1 * ray_multiplier - (ray_multiplier - 1.0);
+            }
+        }
+
+        log::info!("Loading sky pipeline");
+
+        // filled in from ray_details
+        let sky_details_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Sky Details Buffer"),
+                contents: bytemuck::cast_slice(&[SkyDetails::default()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let sky_pipeline = SkyPipeline::new(SkyPipelineDescriptor {
+            device: &device,
+            queue: &queue,
+            resolution: (current_res.0 as u32, current_res.1 as u32),
+            prev_texture_view: ray_pipelines.last().unwrap().output_view(),
+            prev_order_view: ray_pipelines.last().unwrap().order_view(),
+            sky_details_buffer: &sky_details_buffer,
+        });
+
+
+        let bloom_pipeline_count = 5;
+        let bloom_multiplier = 2.0;
+        let mut bloom_pipelines: Vec<BloomPipeline> = Vec::new();
+
+        for i in 0..bloom_pipeline_count*2 {
+            let is_down = i < bloom_pipeline_count;
+
+            current_res = if is_down { 
+                (current_res.0 / bloom_multiplier, current_res.1 / bloom_multiplier)
+            } else {
+                (current_res.0 * bloom_multiplier, current_res.1 * bloom_multiplier)
+            };
+
+            log::info!("Loading bloom pipeline ({} {}): {}, {}", 
+                if i < bloom_pipeline_count {
+                    "Down"
+                } else {
+                    "Up"
+                }, i%bloom_pipeline_count, current_res.0 as u32, current_res.1 as u32);
+
+            let prev_texture_view = match bloom_pipelines.last() {
+                Some(bloom_pipeline) => bloom_pipeline.output_view(),
+                None => sky_pipeline.output_view(),
+            };
+
+            bloom_pipelines.push(
+                BloomPipeline::new(BloomDownPipelineDescriptor {
+                    device: &device,
+                    resolution: (current_res.0 as u32, current_res.1 as u32),
+                    texture_view: prev_texture_view,
+                    direction: if is_down {
+                        BloomDirection::Down
+                    } else {
+                        BloomDirection::Up
+                    },
+                })
+            )
+        }
+
+        let mix_details = MixDetails {
+            mix_ratio: 0.7
+        };
+
+        let mix_details_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Mix Details Buffer"),
+                contents: bytemuck::cast_slice(&[mix_details]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        
+        log::info!("Loading mix pipeline");
+
+        let mix_pipeline = MixPipeline::new(MixPipelineDescriptor {
+            device: &device,
+            resolution: (current_res.0 as u32, current_res.1 as u32),
+            texture_view_1: sky_pipeline.output_view(),
+            texture_view_2: bloom_pipelines.last().unwrap().output_view(),
+            mix_buffer: &mix_details_buffer,
+        });
+
+        log::info!("Loading hdr pipeline");
+
+        let hdr_pipeline = HDRPipeline::new(HDRPipelineDescriptor {
+            device: &device,
+            resolution: (current_res.0 as u32, current_res.1 as u32),
+            texture_view: mix_pipeline.output_view(),
+        });
+
+        log::info!("Loading fxaa pipeline");
+
+        let fxaa_details = FXAADetails::default();
+
+        let fxaa_details_uniform = FXAADetailsUniform::default();
+
+        let fxaa_details_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("FXAA Details Buffer"),
+                contents: bytemuck::cast_slice(&[fxaa_details_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let fxaa_pipeline = FXAAPipeline::new(FXAAPipelineDescriptor {
+            device: &device,
+            resolution: (current_res.0 as u32, current_res.1 as u32),
+            texture_view: hdr_pipeline.output_view(),
+            fxaa_buffer: &fxaa_details_buffer 
+        });
+
+
+        Self {
+            device,
+            queue,
+            resolution,
+            sky_pipeline,
+            sky_details_buffer,
+            hdr_pipeline,
+            bloom_pipelines,
+
+            step_mode: false,
+            step: true,
+
+            ray_pipelines,
+            ray_details,
+            ray_details_uniform,
+            ray_details_buffer,
+
+            deflection_texture,
+            deflection_lut_written: false,
+
+            fxaa_pipeline,
+            fxaa_details,
+            fxaa_details_uniform,
+            fxaa_details_buffer,
+
+            mix_pipeline,
+            mix_details,
+            mix_details_buffer,
+
+            camera_uniform,
+            camera_buffer,
+
+            black_hole_buffer,
+            wormhole_buffer,
+
+            material_buffer,
+            model_buffer,
+        }
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        self.fxaa_pipeline.output_view()
+    }
+
+    pub fn output_texture(&self) -> &wgpu::Texture {
+        self.fxaa_pipeline.output_texture()
+    }
+
+    // per pixel disk crossings, half orbits and the order of the first hit
+    pub fn image_order_view(&self) -> &wgpu::TextureView {
+        self.ray_pipelines.last().unwrap().order_view()
+    }
+
+    fn update_metric(&mut self) {
+        log::info!("Loading metric: {}", String::from(self.ray_details.metric));
+
+        for ray_pipeline in &mut self.ray_pipelines {
+            ray_pipeline.set_metric(&self.device, self.ray_details.metric);
+        }
+    }
+
+    // uploads the scene and settings for the next encode
+    pub fn update(&mut self, scene: &mut Scene) {
+        self.fxaa_details_uniform.update(&self.fxaa_details);
+        self.queue.write_buffer(&self.fxaa_details_buffer, 0, bytemuck::cast_slice(&[self.fxaa_details_uniform]));
+
+        if self.ray_details.metric != self.ray_pipelines[0].metric() {
+            self.update_metric();
+        }
+
+        // built the first time the lut is turned on, most scenes never use it
+        if self.ray_details.deflection_lut != 0 && !self.deflection_lut_written {
+            write_deflection_lut(&self.queue, &self.deflection_texture);
+            self.deflection_lut_written = true;
+        }
+
+        if self.step {
+            self.camera_uniform.update(&scene.camera);
+
+            self.ray_details.material_count = scene.materials.size() as i32;
+            self.ray_details.model_count = scene.models.size() as i32;
+            self.ray_details.black_hole_count = scene.black_holes.size() as i32;
+            self.ray_details.wormhole_count = scene.wormholes.size() as i32;
+            self.ray_details_uniform.update(&self.ray_details);
+
+            self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+            self.queue.write_buffer(&self.ray_details_buffer, 0, bytemuck::cast_slice(&[self.ray_details_uniform]));
+            self.queue.write_buffer(&self.mix_details_buffer, 0, bytemuck::cast_slice(&[self.mix_details]));
+
+            let sky_details = SkyDetails {
+                show_image_order: self.ray_details.show_image_order,
+            };
+
+            self.queue.write_buffer(&self.sky_details_buffer, 0, bytemuck::cast_slice(&[sky_details]));
+
+            let metric = self.ray_details.metric;
+            scene.black_holes.update_buffer_with(&self.queue, &self.black_hole_buffer, |black_hole| black_hole.for_metric(metric));
+            scene.wormholes.update_buffer(&self.queue, &self.wormhole_buffer);
+            scene.models.update_buffer(&self.queue, &self.model_buffer);
+            scene.materials.update_buffer(&self.queue, &self.material_buffer);
+        }
+    }
+
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.step {
+            // compute passes 
+            
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Compute Pass"),
+                    timestamp_writes: None,
+                });
+
+                for rp in &mut self.ray_pipelines {
+                    rp.pass(&mut compute_pass);
+                } 
+
+                self.sky_pipeline.pass(&mut compute_pass);
+            }
+        }
+
+        // render passes 
+
+        for bp in &mut self.bloom_pipelines {
+            bp.pass(encoder);
+        } 
+
+        self.mix_pipeline.pass(encoder);
+        self.hdr_pipeline.pass(encoder);
+        self.fxaa_pipeline.pass(encoder);
+
+        self.step = !self.step_mode;
+    }
+
+    // renders one frame offscreen and reads it back
+    pub async fn render(&mut self, scene: &mut Scene) -> RgbaImage {
+        self.update(scene);
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+
+        self.encode(&mut encoder);
+
+        let output_buffer = self.texture_to_output_buffer(&mut encoder);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        self.read_output_buffer(&output_buffer).await
+    }
+
+    pub fn texture_to_output_buffer(&self, encoder: &mut wgpu::CommandEncoder) -> wgpu::Buffer {
+        let texture = self.fxaa_pipeline.output_texture();
+        let u32_size = std::mem::size_of::<u32>() as u32;
+
+        let output_buffer_size = (u32_size * (texture.width() / 64 * 64 + 64) * texture.height()) as wgpu::BufferAddress;
+
+        let output_buffer_desc = wgpu::BufferDescriptor {
+            size: output_buffer_size,
+            usage: wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::MAP_READ,
+            label: None,
+            mapped_at_creation: false,
+        };
+
+        let output_buffer = self.device.create_buffer(&output_buffer_desc);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(u32_size * (texture.width() / 64 * 64 + 64)),
+                    rows_per_image: Some(texture.height()),
+                },
+            },
+            texture.size(),
+        );
+
+        output_buffer
+    }
+
+    // waits for a buffer from texture_to_output_buffer and crops it to the
+    // requested resolution about the centre
+    pub async fn read_output_buffer(&self, output_buffer: &wgpu::Buffer) -> RgbaImage {
+        let buffer_slice = output_buffer.slice(..);
+
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).unwrap();
+        });
+
+        self.device.poll(wgpu::Maintain::Wait);
+
+        rx.receive().await.unwrap().unwrap();
+
+        let data = buffer_slice.get_mapped_range();
+        let texture = self.fxaa_pipeline.output_texture();
+        let mut img = ImageBuffer::<Rgba<u8>, _>::new(texture.width(), texture.height());
+
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                let i = (y * (texture.width() / 64 * 64 + 64) + x) as usize * 4;
+                img.put_pixel(x, y, Rgba([data[i], data[i+1], data[i+2], 255]));
+            }
+        }
+
+        drop(data);
+        output_buffer.unmap();
+
+        let width = self.resolution.0.min(img.width());
+        let height = self.resolution.1.min(img.height());
+
+        imageops::crop_imm(&img, (img.width() - width) / 2, (img.height() - height) / 2, width, height).to_image()
+    }
+}
+
+fn write_deflection_lut(queue: &wgpu::Queue, texture: &wgpu::Texture) {
+    log::info!("Generating deflection lut");
+
+    let data = deflection::generate();
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(&data),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(16 * DEFLECTION_LUT_IMPACT_SAMPLES),
+            rows_per_image: Some(DEFLECTION_LUT_RADIUS_SAMPLES),
+        },
+        texture.size(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_renders_the_requested_size() {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }));
+
+        // llvmpipe was still compiling the ray shader after a quarter of an
+        // hour and several gigabytes, so only real gpus are tried
+        let Some(adapter) = adapter.filter(|adapter| adapter.get_info().device_type != wgpu::DeviceType::Cpu) else {
+            eprintln!("no gpu adapter, skipping");
+            return;
+        };
+
+        drop(adapter);
+
+        // the scene is too big for the test thread's stack
+        stacker::grow(1024 * 1024 * 1024, || {
+            let mut scene = Scene::new();
+            let mut core = pollster::block_on(RenderCore::headless(&scene, (9, 7)));
+
+            let image = pollster::block_on(core.render(&mut scene));
+
+            assert_eq!(image.dimensions(), (9, 7));
+        });
+    }
+}
@@ -2,7 +2,7 @@ use super::array_buffer::{ArrayBuffer, ArrayBufferUniform};
 
 pub const MAX_MATERIALS: usize = 8;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Material {
     pub color: [f32; 4],
 }
@@ -1,6 +1,6 @@
 use wgpu::TextureView;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum EdgeThresholdMax {
     Low,
     Medium,
@@ -34,7 +34,7 @@ impl From<EdgeThresholdMax> for f32 {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum EdgeThresholdMin {
     Low,
     Medium,
@@ -66,6 +66,8 @@ impl From<EdgeThresholdMin> for f32 {
     }
 }
 
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct FXAADetails {
     pub edge_threshold_min: EdgeThresholdMin,
     pub edge_threshold_max: EdgeThresholdMax,
@@ -73,6 +75,17 @@ pub struct FXAADetails {
     pub subpixel_quality: f32,
 }
 
+impl Default for FXAADetails {
+    fn default() -> Self {
+        Self {
+            edge_threshold_min: EdgeThresholdMin::Ultra,
+            edge_threshold_max: EdgeThresholdMax::Ultra,
+            iterations: 12,
+            subpixel_quality: 0.75,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct FXAADetailsUniform {
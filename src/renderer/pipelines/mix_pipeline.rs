@@ -1,7 +1,7 @@
 use wgpu::TextureView;
 
 #[repr(C)]
-#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize)]
 pub struct MixDetails {
     pub mix_ratio: f32,
 }
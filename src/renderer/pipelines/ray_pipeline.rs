@@ -1,16 +1,162 @@
-use crate::renderer::texture;
+use crate::{renderer::texture, scene::{deflection::{DEFLECTION_LUT_MAX_RADIUS, DEFLECTION_LUT_MIN_RADIUS}, metric::{Metric, MetricKind}}};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum IntegrationMethod {
+    #[default]
+    Euler,
+    CashKarp,
+    RK4,
+    DormandPrince,
+    Verlet,
+}
+
+impl IntegrationMethod {
+    pub fn is_adaptive(&self) -> bool {
+        matches!(self, IntegrationMethod::CashKarp | IntegrationMethod::DormandPrince)
+    }
+}
+
+impl From<IntegrationMethod> for String {
+    fn from(value: IntegrationMethod) -> Self {
+        match value {
+            IntegrationMethod::Euler => "Euler".into(),
+            IntegrationMethod::CashKarp => "Cash-Karp".into(),
+            IntegrationMethod::RK4 => "RK4".into(),
+            IntegrationMethod::DormandPrince => "Dormand-Prince".into(),
+            IntegrationMethod::Verlet => "Verlet".into(),
+        }
+    }
+}
+
+impl From<IntegrationMethod> for i32 {
+    fn from(value: IntegrationMethod) -> Self {
+        match value {
+            IntegrationMethod::Euler => 0,
+            IntegrationMethod::CashKarp => 1,
+            IntegrationMethod::RK4 => 2,
+            IntegrationMethod::DormandPrince => 3,
+            IntegrationMethod::Verlet => 4,
+        }
+    }
+}
+
+// the counts are filled in from the scene every frame so they are not saved
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RayDetails {
+    #[serde(skip)]
+    pub material_count: i32,
+    #[serde(skip)]
+    pub model_count: i32,
+    pub integration_method: IntegrationMethod,
+    pub step_size: f32,
+    pub max_iterations: i32,
+    pub angle_division_threshold: f32,
+    pub highlight_interpolation: i32,
+    #[serde(skip)]
+    pub black_hole_count: i32,
+    pub tolerance: f32,
+    pub error_scale: f32,
+    pub growth_threshold: f32,
+    pub growth_factor: f32,
+    pub min_step: f32,
+    pub max_step: f32,
+    pub lens_meshes: i32,
+    // -1 shows every image, otherwise only light that crossed a disk plane
+    // this many times
+    pub order_filter: i32,
+    pub show_image_order: i32,
+    // skips the integration for rays that stay clear of a lone non spinning
+    // hole, clearance is in photon sphere radii
+    pub deflection_lut: i32,
+    pub deflection_clearance: f32,
+    #[serde(skip)]
+    pub wormhole_count: i32,
+    // blueshifts the sky by the depth of the camera in the potential well
+    pub sky_redshift: i32,
+    // baked into the shader, changing it rebuilds the ray pipelines
+    pub metric: MetricKind,
+}
+
+impl Default for RayDetails {
+    fn default() -> Self {
+        Self {
+            material_count: 0,
+            model_count: 0,
+            integration_method: IntegrationMethod::default(),
+            step_size: 0.15,
+            max_iterations: 2000,
+            angle_division_threshold: 0.02,
+            highlight_interpolation: 0,
+            black_hole_count: 0,
+            tolerance: 1.0,
+            error_scale: 1.0,
+            growth_threshold: 0.00002,
+            growth_factor: 1.0001,
+            min_step: 0.001,
+            max_step: 1.0,
+            lens_meshes: 1,
+            order_filter: -1,
+            show_image_order: 0,
+            deflection_lut: 0,
+            deflection_clearance: 2.0,
+            wormhole_count: 0,
+            sky_redshift: 1,
+            metric: MetricKind::default(),
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct RayDetails {
+pub struct RayDetailsUniform {
     pub material_count: i32,
     pub model_count: i32,
-    pub time: f32,
     pub integration_method: i32,
     pub step_size: f32,
     pub max_iterations: i32,
     pub angle_division_threshold: f32,
     pub highlight_interpolation: i32,
+    pub black_hole_count: i32,
+    pub tolerance: f32,
+    pub error_scale: f32,
+    pub growth_threshold: f32,
+    pub growth_factor: f32,
+    pub min_step: f32,
+    pub max_step: f32,
+    pub lens_meshes: i32,
+    pub order_filter: i32,
+    pub show_image_order: i32,
+    pub deflection_lut: i32,
+    pub deflection_clearance: f32,
+    pub wormhole_count: i32,
+    pub sky_redshift: i32,
+}
+
+impl RayDetailsUniform {
+    pub fn update(&mut self, value: &RayDetails) {
+        self.material_count = value.material_count;
+        self.model_count = value.model_count;
+        self.integration_method = value.integration_method.into();
+        self.step_size = value.step_size;
+        self.max_iterations = value.max_iterations;
+        self.angle_division_threshold = value.angle_division_threshold;
+        self.highlight_interpolation = value.highlight_interpolation;
+        self.black_hole_count = value.black_hole_count;
+        self.tolerance = value.tolerance;
+        self.error_scale = value.error_scale;
+        self.growth_threshold = value.growth_threshold;
+        self.growth_factor = value.growth_factor;
+        self.min_step = value.min_step;
+        self.max_step = value.max_step;
+        self.lens_meshes = value.lens_meshes;
+        self.order_filter = value.order_filter;
+        self.show_image_order = value.show_image_order;
+        self.deflection_lut = value.deflection_lut;
+        self.deflection_clearance = value.deflection_clearance;
+        self.wormhole_count = value.wormhole_count;
+        self.sky_redshift = value.sky_redshift;
+    }
 }
 
 pub struct RayPipelineDescriptor<'a> {
@@ -19,16 +165,23 @@ pub struct RayPipelineDescriptor<'a> {
     pub resolution: (u32, u32),
     pub camera_buffer: &'a wgpu::Buffer,
     pub black_hole_buffer: &'a wgpu::Buffer,
+    pub wormhole_buffer: &'a wgpu::Buffer,
     pub material_buffer: &'a wgpu::Buffer,
     pub model_buffer: &'a wgpu::Buffer,
     pub ray_details_buffer: &'a wgpu::Buffer,
     pub prev_texture_view: &'a wgpu::TextureView,
+    pub prev_order_view: &'a wgpu::TextureView,
+    pub deflection_view: &'a wgpu::TextureView,
+    pub metric: MetricKind,
 }
 
 pub struct RayPipeline {
     pipeline: wgpu::ComputePipeline,
+    pipeline_layout: wgpu::PipelineLayout,
+    metric: MetricKind,
     bind_group: wgpu::BindGroup,
     texture_view: wgpu::TextureView,
+    order_view: wgpu::TextureView,
     resolution: (u32, u32),
 }
 
@@ -36,11 +189,6 @@ impl RayPipeline {
     pub fn new(
         descriptor: RayPipelineDescriptor
     ) -> Self {
-        let shader = descriptor.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader_ray"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/ray.wgsl").into()),
-        });
-
         let texture = descriptor.device.create_texture(&wgpu::TextureDescriptor {
             label: None,
             size: wgpu::Extent3d {
@@ -60,6 +208,22 @@ impl RayPipeline {
 
         let texture_view = texture.create_view(&Default::default());
 
+        let order_texture = descriptor.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: Default::default(),
+        });
+
+        let order_view = order_texture.create_view(&Default::default());
+
         let temp_texture = texture::Texture::from_bytes(
             descriptor.device, descriptor.queue, include_bytes!("../textures/color.png"));
 
@@ -69,6 +233,15 @@ impl RayPipeline {
         let sky_texture = texture::Texture::from_bytes(
             descriptor.device, descriptor.queue, include_bytes!("../textures/sky.png"));
 
+        // seen through wormholes, the same sky unless a second one is built in
+        #[cfg(feature = "sky-other")]
+        let sky_other_bytes = include_bytes!("../textures/sky_other.png");
+        #[cfg(not(feature = "sky-other"))]
+        let sky_other_bytes = include_bytes!("../textures/sky.png");
+
+        let sky_other_texture = texture::Texture::from_bytes(
+            descriptor.device, descriptor.queue, sky_other_bytes);
+
         let bind_group_layout =
             descriptor.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("ray bind group layout"),
@@ -132,12 +305,14 @@ impl RayPipeline {
                         },
                         count: None,
                     },
-                    // black hole
+                    // black holes
                     wgpu::BindGroupLayoutEntry {
                         binding: 5,
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
+                            ty: wgpu::BufferBindingType::Storage {
+                                read_only: true,
+                            },
                             has_dynamic_offset: false,
                             min_binding_size: None,
                         },
@@ -208,6 +383,63 @@ impl RayPipeline {
                         },
                         count: None,
                     },
+                    // image order output
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 13,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: order_texture.format(),
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    // previous iteration image order
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 14,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    // deflection lut
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 15,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    // wormholes
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 16,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage {
+                                read_only: true,
+                            },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // sky on the other side of the wormholes
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 17,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -267,6 +499,26 @@ impl RayPipeline {
                     binding: 12,
                     resource: wgpu::BindingResource::TextureView(sky_texture.view()),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: wgpu::BindingResource::TextureView(&order_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 14,
+                    resource: wgpu::BindingResource::TextureView(descriptor.prev_order_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 15,
+                    resource: wgpu::BindingResource::TextureView(descriptor.deflection_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 16,
+                    resource: descriptor.wormhole_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 17,
+                    resource: wgpu::BindingResource::TextureView(sky_other_texture.view()),
+                },
             ],
         });
 
@@ -279,32 +531,71 @@ impl RayPipeline {
                 push_constant_ranges: &[],
             });
 
-        let pipeline = descriptor.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader,
-            entry_point: "main",
-        });
+        let pipeline = create_pipeline(descriptor.device, &pipeline_layout, descriptor.metric.metric());
 
         Self {
             pipeline,
+            pipeline_layout,
+            metric: descriptor.metric,
             bind_group,
             texture_view,
+            order_view,
             resolution: descriptor.resolution,
         }
     }
 
+    pub fn metric(&self) -> MetricKind {
+        self.metric
+    }
+
+    // the bindings are the same for every metric so only the shader changes
+    pub fn set_metric(&mut self, device: &wgpu::Device, metric: MetricKind) {
+        self.pipeline = create_pipeline(device, &self.pipeline_layout, metric.metric());
+        self.metric = metric;
+    }
+
     pub fn output_view(&self) -> &wgpu::TextureView {
         &self.texture_view
     }
 
+    pub fn order_view(&self) -> &wgpu::TextureView {
+        &self.order_view
+    }
+
     pub fn pass<'a>(&'a mut self, compute_pass: &mut wgpu::ComputePass<'a>) {
         compute_pass.set_pipeline(&self.pipeline);
         compute_pass.set_bind_group(0, &self.bind_group, &[]);
         compute_pass.dispatch_workgroups(
-            (self.resolution.0 + 7) / 8,
-            (self.resolution.1 + 7) / 8,
+            self.resolution.0.div_ceil(8),
+            self.resolution.1.div_ceil(8),
             1,
         );
     }
 }
+
+// ray.wgsl calls into whichever metric is appended to it, and the lut bounds
+// are written in from deflection.rs so the shader can't disagree with the table
+pub fn shader_source(metric: &dyn Metric) -> String {
+    format!(
+        "const DEFLECTION_LUT_MIN_RADIUS = {:?};\nconst DEFLECTION_LUT_MAX_RADIUS = {:?};\n\n{}\n{}\n{}",
+        DEFLECTION_LUT_MIN_RADIUS,
+        DEFLECTION_LUT_MAX_RADIUS,
+        include_str!("../shaders/spectrum.wgsl"),
+        include_str!("../shaders/ray.wgsl"),
+        metric.wgsl(),
+    )
+}
+
+fn create_pipeline(device: &wgpu::Device, pipeline_layout: &wgpu::PipelineLayout, metric: &dyn Metric) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader_ray"),
+        source: wgpu::ShaderSource::Wgsl(shader_source(metric).into()),
+    });
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Compute Pipeline"),
+        layout: Some(pipeline_layout),
+        module: &shader,
+        entry_point: "main",
+    })
+}
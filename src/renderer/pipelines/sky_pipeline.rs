@@ -1,10 +1,23 @@
 use crate::renderer::texture;
 
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyDetails {
+    pub show_image_order: i32,
+}
+
 pub struct SkyPipelineDescriptor<'a> {
     pub device: &'a wgpu::Device, 
     pub queue: &'a wgpu::Queue, 
     pub resolution: (u32, u32),
-    pub prev_texture_view: &'a wgpu::TextureView
+    pub prev_texture_view: &'a wgpu::TextureView,
+    pub prev_order_view: &'a wgpu::TextureView,
+    pub sky_details_buffer: &'a wgpu::Buffer,
+}
+
+// the spectrum helpers are shared with ray.wgsl
+pub fn shader_source() -> String {
+    format!("{}\n{}", include_str!("../shaders/spectrum.wgsl"), include_str!("../shaders/sky.wgsl"))
 }
 
 pub struct SkyPipeline {
@@ -18,7 +31,7 @@ impl SkyPipeline {
     pub fn new(descriptor: SkyPipelineDescriptor) -> Self {
         let shader = descriptor.device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader_sky"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/sky.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(shader_source().into()),
         });
 
         let texture = descriptor.device.create_texture(&wgpu::TextureDescriptor {
@@ -83,6 +96,26 @@ impl SkyPipeline {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -106,6 +139,14 @@ impl SkyPipeline {
                     binding: 3,
                     resource: wgpu::BindingResource::TextureView(descriptor.prev_texture_view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(descriptor.prev_order_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: descriptor.sky_details_buffer.as_entire_binding(),
+                },
             ],
         });
 
@@ -141,8 +182,8 @@ impl SkyPipeline {
         compute_pass.set_pipeline(&self.pipeline);
         compute_pass.set_bind_group(0, &self.bind_group, &[]);
         compute_pass.dispatch_workgroups(
-            (self.resolution.0 + 7) / 8,
-            (self.resolution.1 + 7) / 8,
+            self.resolution.0.div_ceil(8),
+            self.resolution.1.div_ceil(8),
             1,
         );
     }
@@ -0,0 +1,187 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+
+use image::RgbaImage;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordingFormat {
+    PngSequence,
+    Y4m,
+}
+
+impl RecordingFormat {
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".y4m") {
+            RecordingFormat::Y4m
+        } else {
+            RecordingFormat::PngSequence
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::PngSequence => "png",
+            RecordingFormat::Y4m => "y4m",
+        }
+    }
+}
+
+impl From<RecordingFormat> for String {
+    fn from(value: RecordingFormat) -> Self {
+        match value {
+            RecordingFormat::PngSequence => "PNG Sequence".into(),
+            RecordingFormat::Y4m => "Y4M".into(),
+        }
+    }
+}
+
+// replaces the first run of # with the frame number padded to its length
+pub fn frame_path(pattern: &str, frame: u32) -> PathBuf {
+    let Some(start) = pattern.find('#') else {
+        return pattern.into();
+    };
+
+    let width = pattern[start..].chars().take_while(|c| *c == '#').count();
+
+    format!("{}{:0width$}{}", &pattern[..start], frame, &pattern[start + width..], width = width).into()
+}
+
+// numbers a plain path, frame.png becomes frame_####.png
+pub fn sequence_pattern(path: &str) -> String {
+    if path.contains('#') {
+        return path.into();
+    }
+
+    match path.rfind('.') {
+        Some(dot) if !path[dot..].contains(['/', '\\']) => format!("{}_####{}", &path[..dot], &path[dot..]),
+        _ => format!("{}_####", path),
+    }
+}
+
+// uncompressed yuv 4:4:4 frames, bt.601 limited range
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    resolution: (u32, u32),
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut writer: W, resolution: (u32, u32), fps: u32) -> io::Result<Self> {
+        writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444 XCOLORRANGE=LIMITED", resolution.0, resolution.1, fps)?;
+
+        Ok(Self {
+            writer,
+            resolution,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        if image.dimensions() != self.resolution {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame size changed during the stream"));
+        }
+
+        let pixels = image.pixels().count();
+        let mut planes = vec![0u8; pixels * 3];
+        let (y_plane, chroma) = planes.split_at_mut(pixels);
+        let (u_plane, v_plane) = chroma.split_at_mut(pixels);
+
+        for (i, pixel) in image.pixels().enumerate() {
+            let [r, g, b, _] = pixel.0.map(|c| c as f32);
+
+            y_plane[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+            u_plane[i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+            v_plane[i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&planes)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// a fixed number of frames a fixed dt apart, so the same scene always
+// records the same frames however fast they render
+pub struct Recording {
+    pub format: RecordingFormat,
+    pub path: String,
+    pub frame_count: u32,
+    pub fps: u32,
+    frame: u32,
+    y4m: Option<Y4mWriter<BufWriter<File>>>,
+}
+
+impl Recording {
+    pub fn new(format: RecordingFormat, path: String, frame_count: u32, fps: u32) -> Self {
+        let path = match format {
+            RecordingFormat::PngSequence => sequence_pattern(&path),
+            RecordingFormat::Y4m => path,
+        };
+
+        Self {
+            format,
+            path,
+            frame_count,
+            fps: fps.max(1),
+            frame: 0,
+            y4m: None,
+        }
+    }
+
+    pub fn dt(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.frame_count
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        match self.format {
+            RecordingFormat::PngSequence => {
+                let path = frame_path(&self.path, self.frame);
+                create_parent(&path)?;
+
+                image.save(&path).map_err(io::Error::other)?;
+            },
+            RecordingFormat::Y4m => {
+                if self.y4m.is_none() {
+                    let path = Path::new(&self.path);
+                    create_parent(path)?;
+
+                    let file = BufWriter::new(File::create(path)?);
+                    self.y4m = Some(Y4mWriter::new(file, image.dimensions(), self.fps)?);
+                }
+
+                self.y4m.as_mut().unwrap().write_frame(image)?;
+            },
+        }
+
+        self.frame += 1;
+
+        if self.is_finished() {
+            self.finish()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(mut y4m) = self.y4m.take() {
+            y4m.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
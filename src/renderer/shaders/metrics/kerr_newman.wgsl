@@ -0,0 +1,11 @@
+// kerr-newman, spin and charge together. Needs kerr_schild.wgsl
+
+const METRIC_USES_LUT = false;
+
+fn metric_acceleration(r: vec3<f32>, direction: vec3<f32>, black_hole: BlackHole) -> vec3<f32> {
+    return kerr_schild_acceleration(r, direction, black_hole, black_hole.charge);
+}
+
+fn metric_lapse(r: vec3<f32>, black_hole: BlackHole) -> f32 {
+    return kerr_schild_lapse(r, black_hole, black_hole.charge);
+}
@@ -0,0 +1,73 @@
+// kerr-newman in kerr-schild form, g = η + f l l with the spin vector J/M,
+// shared by the spinning metrics. Light follows the christoffel symbols of
+// that metric, which depend on the direction of travel, so rays going with
+// the spin are dragged along. See kerr_schild_acceleration in
+// src/scene/metric.rs for the cpu version
+
+// kerr-schild radius of p, the horizon is the oblate spheroid r = r+
+fn kerr_schild_radius(p: vec3<f32>, spin: vec3<f32>) -> f32 {
+    let z = dot(p, spin);
+    let w = dot(p, p) - dot(spin, spin);
+
+    return sqrt(0.5 * w + sqrt(0.25 * w * w + z * z));
+}
+
+fn kerr_schild_spin(black_hole: BlackHole) -> vec3<f32> {
+    return black_hole.angular_momentum / black_hole.mass;
+}
+
+fn kerr_schild_acceleration(p: vec3<f32>, direction: vec3<f32>, black_hole: BlackHole, q: f32) -> vec3<f32> {
+    let m = black_hole.mass;
+    let spin = kerr_schild_spin(black_hole);
+    let a2 = dot(spin, spin);
+    let q2 = q * q;
+
+    let z = dot(p, spin);
+    let r = kerr_schild_radius(p, spin);
+    let r2 = r * r;
+    let k = r2 + a2;
+    let den = r2 * r2 + z * z;
+
+    let n = r2 * (2.0 * m * r - q2);
+    let f = n / den;
+    let l = (r * p + cross(p, spin) + z * spin / r) / k;
+
+    let grad_r = r * (r2 * p + z * spin) / den;
+    let df_dr = (2.0 * r * (3.0 * m * r - q2) * den - n * 4.0 * r * r2) / (den * den);
+    let grad_f = df_dr * grad_r - 2.0 * n * z / (den * den) * spin;
+    let dl_dr = (p - z * spin / r2 - 2.0 * r * l) / k;
+
+    // photons arrive along -direction, the traced ray runs them backwards
+    let v = -direction;
+    let lv = dot(l, v);
+
+    // u^t from g(u, u) = 0, the root that stays future pointing in the ergosphere
+    let a = f - 1.0;
+    let b = 2.0 * f * lv;
+    let c = dot(v, v) + f * lv * lv;
+    let u_t = 2.0 * c / (-b + sqrt(max(b * b - 4.0 * a * c, 0.0)));
+
+    let s = u_t + lv;
+
+    // derivatives of l.u and f along v and across space with u held fixed
+    let dl_v = dl_dr * dot(grad_r, v) + (r * v + cross(v, spin) + dot(v, spin) * spin / r) / k;
+    let ds_v = dot(dl_v, v);
+    let df_v = dot(grad_f, v);
+    let grad_s = dot(dl_dr, v) * grad_r + (r * v + cross(spin, v) + dot(v, spin) * spin / r) / k;
+
+    // Γ_μαβ u^α u^β, then raised with g^μν = η^μν - f l^μ l^ν
+    let lower_t = df_v * s + f * ds_v;
+    let lower = lower_t * l + f * s * dl_v - 0.5 * (grad_f * s * s + 2.0 * f * s * grad_s);
+
+    return -(lower - f * (dot(l, lower) - lower_t) * l);
+}
+
+// sqrt(-g_tt), which reaches zero at the ergosphere rather than the horizon
+fn kerr_schild_lapse(p: vec3<f32>, black_hole: BlackHole, q: f32) -> f32 {
+    let spin = kerr_schild_spin(black_hole);
+    let z = dot(p, spin);
+    let r = kerr_schild_radius(p, spin);
+    let f = r * r * (2.0 * black_hole.mass * r - q * q) / (r * r * r * r + z * z);
+
+    return sqrt(max(1.0 - f, 0.0));
+}
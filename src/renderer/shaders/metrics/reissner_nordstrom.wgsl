@@ -0,0 +1,19 @@
+// reissner-nordstrom, u'' + u = 3Mu^2 - 2Q^2u^3
+
+const METRIC_USES_LUT = false;
+
+fn metric_acceleration(r: vec3<f32>, direction: vec3<f32>, black_hole: BlackHole) -> vec3<f32> {
+    let dist = length(r);
+    let h2 = pow(length(cross(r, direction)), 2.0);
+    let q = black_hole.charge;
+
+    return -3.0 * black_hole.mass * h2 * r / pow(dist, 5.0)
+        + 2.0 * q * q * h2 * r / pow(dist, 6.0);
+}
+
+fn metric_lapse(r: vec3<f32>, black_hole: BlackHole) -> f32 {
+    let dist = length(r);
+    let q = black_hole.charge;
+
+    return sqrt(max(1.0 - 2.0 * black_hole.mass / dist + q * q / (dist * dist), 0.0));
+}
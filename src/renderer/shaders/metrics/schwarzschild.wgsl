@@ -0,0 +1,17 @@
+// schwarzschild, only the mass of each hole is used
+
+// the deflection lut is integrated in this metric
+const METRIC_USES_LUT = true;
+
+fn metric_acceleration(r: vec3<f32>, direction: vec3<f32>, black_hole: BlackHole) -> vec3<f32> {
+    let dist = length(r);
+    let h2 = pow(length(cross(r, direction)), 2.0);
+
+    return -3.0 * black_hole.mass * h2 * r / pow(dist, 5.0);
+}
+
+fn metric_lapse(r: vec3<f32>, black_hole: BlackHole) -> f32 {
+    let dist = length(r);
+
+    return sqrt(max(1.0 - 2.0 * black_hole.mass / dist, 0.0));
+}
@@ -1,13 +1,14 @@
 const MAX_MODEL_VERTICES = 524288;
 const MAX_MODELS = 1;
 const MAX_MATERIALS = 8;
+const MAX_BLACK_HOLES = 4;
 
 @group(0) @binding(0) var color_buffer: texture_storage_2d<rgba32float, write>;
 @group(0) @binding(1) var<uniform> camera: Camera;
 @group(0) @binding(2) var<uniform> details: Details;
 @group(0) @binding(3) var<storage, read> materials: array<Material, MAX_MATERIALS>;
 @group(0) @binding(4) var<storage, read> models: array<Model, MAX_MODELS>;
-@group(0) @binding(5) var<uniform> black_hole: BlackHole;
+@group(0) @binding(5) var<storage, read> black_holes: array<BlackHole, MAX_BLACK_HOLES>;
 
 @group(0) @binding(6) var s_temp: sampler;
 @group(0) @binding(7) var t_temp: texture_2d<f32>;
@@ -18,6 +19,18 @@ const MAX_MATERIALS = 8;
 
 @group(0) @binding(10) var t_prev: texture_2d<f32>;
 
+// x: disk plane crossings, y: half orbits around the nearest hole,
+// z: image order of the first visible hit or -1
+@group(0) @binding(13) var order_buffer: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(14) var t_prev_order: texture_2d<f32>;
+
+@group(0) @binding(16) var<storage, read> wormholes: array<Wormhole>;
+@group(0) @binding(17) var t_sky_other: texture_2d<f32>;
+
+// (swept angle, minimum radius / M, 0, valid) by impact parameter over entry
+// radius and entry radius, see scene/deflection.rs
+@group(0) @binding(15) var t_deflection: texture_2d<f32>;
+
 struct Material {
     color: vec4<f32>
 };
@@ -25,12 +38,30 @@ struct Material {
 struct Details {
     material_count: i32,
     model_count: i32,
-    time: f32,
-    integration_method: i32, // 0: eulers, 1: rk
+    integration_method: i32,
     step_size: f32,
     max_iterations: i32,
     angle_division_threshold: f32,
     highlight_interpolation: i32,
+    black_hole_count: i32,
+    tolerance: f32,
+    error_scale: f32,
+    growth_threshold: f32,
+    growth_factor: f32,
+    min_step: f32,
+    max_step: f32,
+    lens_meshes: i32,
+    order_filter: i32,
+    show_image_order: i32,
+    deflection_lut: i32,
+    deflection_clearance: f32,
+    wormhole_count: i32,
+    sky_redshift: i32,
+}
+
+struct TraceResult {
+    color: vec4<f32>,
+    order: vec4<f32>,
 }
 
 struct Ray {
@@ -38,10 +69,36 @@ struct Ray {
     direction: vec3<f32>,
 };
 
+struct Wormhole {
+    position: vec3<f32>,
+    throat_radius: f32,
+    relativity_radius: f32,
+};
+
+struct WormholeExit {
+    ray: Ray,
+    // came out on the far side
+    through: bool,
+    // circling the throat
+    trapped: bool,
+    swept_angle: f32,
+};
+
+struct Deflection {
+    valid: bool,
+    ray: Ray,
+    swept_angle: f32,
+    crossings: i32,
+    closest_position: vec3<f32>,
+};
+
 struct Camera {
     position: vec3<f32>,
     forward: vec3<f32>,
     fov: f32,
+    velocity: vec3<f32>,
+    aberration: i32,
+    doppler: i32,
 };
 
 struct Spherical {
@@ -112,7 +169,7 @@ struct Torus2D {
 struct BlackHole {
     inner_radius: f32,
     outer_radius: f32,
-    rotation_speed: f32,
+    disk_phase: f32,
     relativity_radius: f32,
     position: vec3<f32>,
     show_disk_texture: i32,
@@ -120,6 +177,31 @@ struct BlackHole {
     show_red_shift: i32,
     rotation_matrix: mat3x3<f32>,
     feather_amount: f32,
+    horizon_radius: f32,
+    spin: f32,
+    mass: f32,
+    angular_momentum: vec3<f32>,
+    photon_sphere_radius: f32,
+    isco_radius: f32,
+    show_photon_sphere: i32,
+    show_isco: i32,
+    show_horizon: i32,
+    show_relativity_sphere: i32,
+    volumetric_disk: i32,
+    disk_scale_height: f32,
+    disk_density: f32,
+    disk_noise_scale: f32,
+    show_jets: i32,
+    jet_length: f32,
+    jet_opening_angle: f32,
+    jet_brightness: f32,
+    jet_lorentz_factor: f32,
+    temperature_profile: i32,
+    accretion_rate: f32,
+    disk_temperature: f32,
+    beaming_exponent: i32,
+    beaming_exaggeration: f32,
+    charge: f32,
 }
 
 struct Sphere {
@@ -164,6 +246,68 @@ const b_a_4 = 13525.0/55296.0;
 const b_a_5 = 277.0/14336.0;
 const b_a_6 = 1.0/4.0;
 
+// dormand-prince 5(4), the 5th order weights are the last row of the tableau
+const dp_a_21 = 1.0/5.0;
+
+const dp_a_31 = 3.0/40.0;
+const dp_a_32 = 9.0/40.0;
+
+const dp_a_41 = 44.0/45.0;
+const dp_a_42 = -56.0/15.0;
+const dp_a_43 = 32.0/9.0;
+
+const dp_a_51 = 19372.0/6561.0;
+const dp_a_52 = -25360.0/2187.0;
+const dp_a_53 = 64448.0/6561.0;
+const dp_a_54 = -212.0/729.0;
+
+const dp_a_61 = 9017.0/3168.0;
+const dp_a_62 = -355.0/33.0;
+const dp_a_63 = 46732.0/5247.0;
+const dp_a_64 = 49.0/176.0;
+const dp_a_65 = -5103.0/18656.0;
+
+const dp_a_71 = 35.0/384.0;
+const dp_a_73 = 500.0/1113.0;
+const dp_a_74 = 125.0/192.0;
+const dp_a_75 = -2187.0/6784.0;
+const dp_a_76 = 11.0/84.0;
+
+const dp_e_1 = 71.0/57600.0;
+const dp_e_3 = -71.0/16695.0;
+const dp_e_4 = 71.0/1920.0;
+const dp_e_5 = -17253.0/339200.0;
+const dp_e_6 = 22.0/525.0;
+const dp_e_7 = -1.0/40.0;
+
+const PHOTON_SPHERE_COLOR = vec3<f32>(1.0, 0.6, 0.1);
+const ISCO_COLOR = vec3<f32>(0.1, 0.8, 1.0);
+const HORIZON_COLOR = vec3<f32>(1.0, 0.1, 0.1);
+const RELATIVITY_SPHERE_COLOR = vec3<f32>(0.2, 1.0, 0.3);
+
+const VOLUME_SAMPLES = 4;
+
+const BLACKBODY_SAMPLES = 10;
+const DISK_PROFILE_PEAK = 0.48787;
+
+const JET_COLOR = vec3<f32>(0.55, 0.7, 1.0);
+
+const INTEGRATION_EULER = 0;
+const INTEGRATION_CASH_KARP = 1;
+const INTEGRATION_RK4 = 2;
+const INTEGRATION_DORMAND_PRINCE = 3;
+const INTEGRATION_VERLET = 4;
+
+// doppler factor of the camera for the ray being traced, set by create_ray
+var<private> observer_shift: f32 = 1.0;
+// observer_shift times the blueshift of light falling in to the camera
+var<private> sky_shift: f32 = 1.0;
+
+const WORMHOLE_MAX_STEPS = 400;
+
+// DEFLECTION_LUT_MIN_RADIUS and DEFLECTION_LUT_MAX_RADIUS are prepended from
+// deflection.rs by ray_pipeline::shader_source
+
 @compute @workgroup_size(8,8,1)
 fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     let screen_size: vec2<i32> = vec2<i32>(textureDimensions(color_buffer));
@@ -178,8 +322,7 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     if all(t_prev_size == vec2<i32>(1)) {
         // base case
         let ray = create_ray(screen_pos, screen_size);
-        let color = trace_ray(ray);
-        textureStore(color_buffer, screen_pos, color);
+        store_trace(screen_pos, trace_ray(ray));
     } else {
         // recursive
         let sf = (screen_size - 1) / (t_prev_size - 1);
@@ -189,9 +332,11 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
         let prev_pos_tl: vec2<f32> = floor(prev_pos);
 
         let color_tl = textureLoad(t_prev, vec2<i32>(prev_pos_tl), 0);
+        let order_tl = textureLoad(t_prev_order, vec2<i32>(prev_pos_tl), 0);
 
         if all(abs(prev_pos_tl - prev_pos) < vec2<f32>(0.001)) {
             textureStore(color_buffer, screen_pos, color_tl);
+            textureStore(order_buffer, screen_pos, order_tl);
         } else {
             let prev_pos_bl: vec2<f32> = prev_pos_tl + vec2<f32>(0.0, 1.0);
             let prev_pos_tr: vec2<f32> = prev_pos_tl + vec2<f32>(1.0, 0.0);
@@ -214,7 +359,7 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
 
             let alphas = vec4<f32>(color_tl.a, color_tr.a, color_bl.a, color_br.a);
 
-            if all(alphas == vec4<f32>(0.0)) && all(angles_between < vec4<f32>(details.angle_division_threshold)) {
+            if all(alphas < vec4<f32>(0.0)) && all(angles_between < vec4<f32>(details.angle_division_threshold)) {
                 let t = prev_pos - prev_pos_tl;
 
                 let uv_tl = color_tl.rgb;
@@ -226,22 +371,37 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
                 let uv_b = mix(uv_bl, uv_br, t.x);
 
                 let p = mix(uv_t, uv_b, t.y);
+                let shift = mix(mix(color_tl.a, color_tr.a, t.x), mix(color_bl.a, color_br.a, t.x), t.y);
 
                 if details.highlight_interpolation != 0 {
-                    textureStore(color_buffer, screen_pos, vec4<f32>(p, 0.0));
+                    textureStore(color_buffer, screen_pos, vec4<f32>(p, shift));
                 } else {
-                    textureStore(color_buffer, screen_pos, vec4<f32>(p, 0.0));
+                    textureStore(color_buffer, screen_pos, vec4<f32>(p, shift));
                 }
+
+                // only rays that escaped without a hit get interpolated, they
+                // share the order of their neighbours
+                textureStore(order_buffer, screen_pos, order_tl);
             } else {
                 let ray = create_ray(screen_pos, screen_size);
-                let color = trace_ray(ray);
-                textureStore(color_buffer, screen_pos, color);
+                store_trace(screen_pos, trace_ray(ray));
             }
         }
     }
 
 }
 
+// show_image_order is drawn by the sky pass, the alpha here still has to
+// mark escaped rays for the levels above
+fn store_trace(screen_pos: vec2<i32>, result: TraceResult) {
+    textureStore(color_buffer, screen_pos, result.color);
+    textureStore(order_buffer, screen_pos, result.order);
+}
+
+fn order_visible(order: i32) -> bool {
+    return details.order_filter < 0 || order == details.order_filter;
+}
+
 fn spherical_to_cartesian(spherical: vec3<f32>) -> vec3<f32> {
     let sinTheta = sin(spherical.y);
 
@@ -278,17 +438,79 @@ fn create_ray(screen_pos: vec2<i32>, screen_size: vec2<i32>) -> Ray {
 
     let fov_factor = 1.0 / tan(camera.fov / 2.0);
 
-    let ray_dir = normalize(pos.x*right + pos.y*up + camera.forward*fov_factor);
+    var ray_dir = normalize(pos.x*right + pos.y*up + camera.forward*fov_factor);
     let ray_pos = camera.position;
 
+    if camera.aberration != 0 {
+        ray_dir = aberrate(ray_dir);
+    }
+
+    observer_shift = 1.0;
+
+    if camera.doppler != 0 {
+        observer_shift = observer_doppler(ray_dir);
+    }
+
+    sky_shift = observer_shift;
+
+    if details.sky_redshift != 0 {
+        sky_shift *= gravitational_blueshift(ray_pos);
+    }
+
     return Ray(ray_pos, ray_dir);
 }
 
+// frequency a static observer at position sees over what the light had far
+// away, each hole deepens the well
+fn gravitational_blueshift(position: vec3<f32>) -> f32 {
+    var lapse = 1.0;
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        lapse *= metric_lapse(position - black_holes[i].position, black_holes[i]);
+    }
+
+    // inside a horizon there is no static observer
+    return 1.0 / max(lapse, 0.05);
+}
+
+// ray direction seen by the moving camera to the one a static observer at
+// the same place would see, photons arrive along -direction
+fn aberrate(direction: vec3<f32>) -> vec3<f32> {
+    let beta = length(camera.velocity);
+
+    if beta < 1e-6 {
+        return direction;
+    }
+
+    let gamma = inverseSqrt(1.0 - beta * beta);
+    let n = camera.velocity / beta;
+    let k = -direction;
+
+    let k_static = (k + (gamma - 1.0) * dot(k, n) * n + gamma * camera.velocity) / (gamma * (1.0 + dot(camera.velocity, k)));
+
+    return -normalize(k_static);
+}
+
+// observed over static frequency for light arriving from direction, in the
+// static frame
+fn observer_doppler(direction: vec3<f32>) -> f32 {
+    let beta = min(length(camera.velocity), 0.999);
+    let gamma = inverseSqrt(1.0 - beta * beta);
+
+    return gamma * (1.0 + dot(camera.velocity, direction));
+}
+
 fn trace_ray_model(ray: Ray, model_index: i32, t_min: f32, t_max: f32) -> RenderState {
     var closest_render_state: RenderState;
     closest_render_state.t = t_max;
 
     var node: Node = models[model_index].nodes[0];
+
+    // most curved steps miss the model entirely
+    if hit_aabb(ray, node, models[model_index].position) > t_max {
+        return closest_render_state;
+    }
+
     var stack: array<Node, 19>;
     var stack_location: u32 = 0;
 
@@ -362,15 +584,19 @@ fn trace_ray_model(ray: Ray, model_index: i32, t_min: f32, t_max: f32) -> Render
     return closest_render_state;
 }
 
-fn hit_ray(ray: Ray, t_min: f32, t_max: f32, ray_distance: f32, render_triangles: bool, render_black_hole: bool) -> RenderState {
+fn hit_ray(ray: Ray, t_min: f32, t_max: f32, render_triangles: bool, render_black_hole: bool) -> RenderState {
     var closest_render_state: RenderState;
     closest_render_state.t = t_max;
 
-    let render_state = hit_black_hole(ray, black_hole, t_min, t_max, ray_distance);
-
-    if render_state.hit && render_state.t < closest_render_state.t && render_black_hole {
-        closest_render_state = render_state;
+    if render_black_hole {
+        for(var i = 0; i < details.black_hole_count; i++) {
+            let observer = camera.position - black_holes[i].position;
+            let render_state = hit_black_hole(ray, black_holes[i], t_min, t_max, observer);
 
+            if render_state.hit && render_state.t < closest_render_state.t {
+                closest_render_state = render_state;
+            }
+        }
     }
 
     if render_triangles {
@@ -396,18 +622,69 @@ struct RKState {
     h: f32,
     e_max: f32,
     ray: Ray,
+    // f at ray, reused as the first stage by dormand-prince
+    acceleration: vec3<f32>,
+}
+
+// metric_acceleration and metric_lapse come from the metric the pipeline
+// appends to this file, see src/renderer/shaders/metrics
+fn f(rayPos: vec3<f32>, rayDir: vec3<f32>) -> vec3<f32> {
+    var acceleration = vec3<f32>(0.0);
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        acceleration += metric_acceleration(rayPos - black_holes[i].position, rayDir, black_holes[i]);
+    }
+
+    return acceleration;
+}
+
+fn shrink_step(h: f32, e_max: f32, exponent: f32) -> f32 {
+    let h_temp = 0.9 * h / pow(e_max, exponent);
+
+    if h >= 0.0 {
+        return max(h_temp, 0.1 * h);
+    } else {
+        return min(h_temp, 0.1 * h);
+    }
+}
+
+fn grow_step(h: f32, e_max: f32) -> f32 {
+    if e_max > details.growth_threshold {
+        return h * 0.9 * pow(e_max, -0.001);
+    } else {
+        return h * details.growth_factor;
+    }
+}
+
+fn clamp_step(h: f32) -> f32 {
+    return clamp(h, details.min_step, details.max_step);
+}
+
+// longest step that can't carry the ray over a horizon, it halves the gap each
+// step and min_step finishes it off
+fn horizon_step(position: vec3<f32>) -> f32 {
+    var limit = details.max_step;
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        let gap = max(distance(position, black_holes[i].position) - horizon_equatorial_radius(black_holes[i]), 0.0);
+
+        limit = min(limit, 0.5 * gap + details.min_step);
+    }
+
+    return limit;
 }
 
-fn f(rayPos: vec3<f32>, h2: f32, dist: f32) -> vec3<f32> {
-    return -1.5 * h2 * (rayPos-black_hole.position) / pow(dist, 5.0);
+fn error_max(e: vec3<f32>, yscal: vec3<f32>) -> f32 {
+    return max(max(abs(e.x/yscal.x), abs(e.y/yscal.y)), abs(e.z/yscal.z));
 }
 
-fn next_ray_rk(rk_state_in: RKState) -> RKState {
+fn next_ray_cash_karp(rk_state_in: RKState) -> RKState {
     var rk_state = rk_state_in;
 
-    let ray = rk_state.ray;
+    // the first step comes straight from the settings
+    rk_state.h = min(clamp_step(rk_state.h), horizon_step(rk_state.ray.position));
 
-    let dist = length(ray.position - black_hole.position);
+    let ray = rk_state.ray;
 
     var k_1 = vec3<f32>(0.0);
     var k_2 = vec3<f32>(0.0);
@@ -416,38 +693,30 @@ fn next_ray_rk(rk_state_in: RKState) -> RKState {
     var k_5 = vec3<f32>(0.0);
     var k_6 = vec3<f32>(0.0);
 
-    let h2 = pow(length(cross(ray.position, ray.direction)), 2.0);
-
-    let dydx = f(ray.position, h2, dist);
-    let yscal = vec3<f32>(1.0); //abs(ray.position) + abs(dydx * h);
-    let eps = 1.0;
+    let dydx = f(ray.position, ray.direction);
+    let yscal = vec3<f32>(details.error_scale);
+    let eps = details.tolerance;
 
     while true {
         let h = rk_state.h;
 
         k_1 = dydx;
-        k_2 = f(ray.position + (a_21*k_1)*h, h2, dist);
-        k_3 = f(ray.position + (a_31*k_1 + a_32*k_2)*h, h2, dist);
-        k_4 = f(ray.position + (a_41*k_1 + a_42*k_2 + a_43*k_2)*h, h2, dist);
-        k_5 = f(ray.position + (a_51*k_1 + a_52*k_2 + a_53*k_3 + a_54*k_4)*h, h2, dist);
-        k_6 = f(ray.position + (a_61*k_1 + a_62*k_2 + a_63*k_3 + a_64*k_4 + a_65*k_5)*h, h2, dist);
+        k_2 = f(ray.position + (a_21*k_1)*h, ray.direction);
+        k_3 = f(ray.position + (a_31*k_1 + a_32*k_2)*h, ray.direction);
+        k_4 = f(ray.position + (a_41*k_1 + a_42*k_2 + a_43*k_3)*h, ray.direction);
+        k_5 = f(ray.position + (a_51*k_1 + a_52*k_2 + a_53*k_3 + a_54*k_4)*h, ray.direction);
+        k_6 = f(ray.position + (a_61*k_1 + a_62*k_2 + a_63*k_3 + a_64*k_4 + a_65*k_5)*h, ray.direction);
 
         let e = h * ((b_1-b_a_1)*k_1 + (b_2-b_a_2)*k_2 + (b_3-b_a_3)*k_3 + (b_4-b_a_4)*k_4 + (b_5-b_a_5)*k_5 + (b_6-b_a_6)*k_6);
 
-        rk_state.e_max = max(max(abs(e.x/yscal.x), abs(e.y/yscal.y)), abs(e.z/yscal.z));
-        rk_state.e_max = rk_state.e_max / eps;
+        rk_state.e_max = error_max(e, yscal) / eps;
 
-        if rk_state.e_max <= 1.0 {
+        // at the smallest step the error is accepted so the ray can't stall
+        if rk_state.e_max <= 1.0 || h <= details.min_step {
             break;
-        } 
-
-        let h_temp = 0.9 * h / pow(rk_state.e_max, 0.25);
-
-        if h >= 0.0 {
-            rk_state.h = max(h_temp, h);
-        } else {
-            rk_state.h = min(h_temp, h);
         }
+
+        rk_state.h = clamp_step(shrink_step(h, rk_state.e_max, 0.25));
     }
 
     rk_state.ray.direction += rk_state.h * (b_a_1*k_1 + b_a_2*k_2 + b_a_3*k_3 + b_a_4*k_4 + b_a_5*k_5 + b_a_6*k_6);
@@ -455,23 +724,123 @@ fn next_ray_rk(rk_state_in: RKState) -> RKState {
 
     rk_state.ray.position += ray.direction * rk_state.h;
 
-    if rk_state.e_max > 0.00002 {
-        rk_state.h *= 0.9 * pow(rk_state.e_max, -0.001);
-    } else {
-        rk_state.h *= 1.0001;
+    rk_state.h = clamp_step(grow_step(rk_state.h, rk_state.e_max));
+
+    return rk_state;
+}
+
+// integrates position and direction together, the 7th stage is evaluated at
+// the accepted point so it becomes the first stage of the next step (FSAL)
+fn next_ray_dormand_prince(rk_state_in: RKState) -> RKState {
+    var rk_state = rk_state_in;
+
+    rk_state.h = min(clamp_step(rk_state.h), horizon_step(rk_state.ray.position));
+
+    let ray = rk_state.ray;
+
+    let x = ray.position;
+    let v = ray.direction;
+
+    let kx_1 = v;
+    let kv_1 = rk_state.acceleration;
+
+    var kx_7 = vec3<f32>(0.0);
+    var kv_7 = vec3<f32>(0.0);
+    var x_new = vec3<f32>(0.0);
+
+    let yscal = vec3<f32>(details.error_scale);
+    let eps = details.tolerance;
+
+    while true {
+        let h = rk_state.h;
+
+        let kx_2 = v + h*(dp_a_21*kv_1);
+        let kv_2 = f(x + h*(dp_a_21*kx_1), kx_2);
+
+        let kx_3 = v + h*(dp_a_31*kv_1 + dp_a_32*kv_2);
+        let kv_3 = f(x + h*(dp_a_31*kx_1 + dp_a_32*kx_2), kx_3);
+
+        let kx_4 = v + h*(dp_a_41*kv_1 + dp_a_42*kv_2 + dp_a_43*kv_3);
+        let kv_4 = f(x + h*(dp_a_41*kx_1 + dp_a_42*kx_2 + dp_a_43*kx_3), kx_4);
+
+        let kx_5 = v + h*(dp_a_51*kv_1 + dp_a_52*kv_2 + dp_a_53*kv_3 + dp_a_54*kv_4);
+        let kv_5 = f(x + h*(dp_a_51*kx_1 + dp_a_52*kx_2 + dp_a_53*kx_3 + dp_a_54*kx_4), kx_5);
+
+        let kx_6 = v + h*(dp_a_61*kv_1 + dp_a_62*kv_2 + dp_a_63*kv_3 + dp_a_64*kv_4 + dp_a_65*kv_5);
+        let kv_6 = f(x + h*(dp_a_61*kx_1 + dp_a_62*kx_2 + dp_a_63*kx_3 + dp_a_64*kx_4 + dp_a_65*kx_5), kx_6);
+
+        kx_7 = v + h*(dp_a_71*kv_1 + dp_a_73*kv_3 + dp_a_74*kv_4 + dp_a_75*kv_5 + dp_a_76*kv_6);
+        x_new = x + h*(dp_a_71*kx_1 + dp_a_73*kx_3 + dp_a_74*kx_4 + dp_a_75*kx_5 + dp_a_76*kx_6);
+        kv_7 = f(x_new, kx_7);
+
+        let e_x = h * (dp_e_1*kx_1 + dp_e_3*kx_3 + dp_e_4*kx_4 + dp_e_5*kx_5 + dp_e_6*kx_6 + dp_e_7*kx_7);
+        let e_v = h * (dp_e_1*kv_1 + dp_e_3*kv_3 + dp_e_4*kv_4 + dp_e_5*kv_5 + dp_e_6*kv_6 + dp_e_7*kv_7);
+
+        rk_state.e_max = max(error_max(e_x, yscal), error_max(e_v, yscal)) / eps;
+
+        if rk_state.e_max <= 1.0 || h <= details.min_step {
+            break;
+        }
+
+        rk_state.h = clamp_step(shrink_step(h, rk_state.e_max, 0.2));
     }
 
+    rk_state.ray.position = x_new;
+    rk_state.ray.direction = normalize(kx_7);
+    rk_state.acceleration = kv_7;
+
+    rk_state.h = clamp_step(grow_step(rk_state.h, rk_state.e_max));
+
     return rk_state;
 }
 
-fn next_ray_euler(in_ray: Ray, step_size: f32) -> Ray {
+fn next_ray_rk4(in_ray: Ray, step_size: f32) -> Ray {
     var ray = in_ray;
 
-    let h2 = pow(length(cross(ray.position, ray.direction)), 2.0);
+    let h = step_size;
+    let x = ray.position;
+    let v = ray.direction;
+
+    let kx_1 = v;
+    let kv_1 = f(x, kx_1);
+
+    let kx_2 = v + 0.5*h*kv_1;
+    let kv_2 = f(x + 0.5*h*kx_1, kx_2);
 
-    let dist = length(ray.position - black_hole.position);
+    let kx_3 = v + 0.5*h*kv_2;
+    let kv_3 = f(x + 0.5*h*kx_2, kx_3);
 
-    ray.direction += f(ray.position, h2, dist) * step_size;
+    let kx_4 = v + h*kv_3;
+    let kv_4 = f(x + h*kx_3, kx_4);
+
+    ray.position += h/6.0 * (kx_1 + 2.0*kx_2 + 2.0*kx_3 + kx_4);
+    ray.direction = normalize(v + h/6.0 * (kv_1 + 2.0*kv_2 + 2.0*kv_3 + kv_4));
+
+    return ray;
+}
+
+// kick-drift-kick leapfrog. The force depends on the direction only through
+// h = |r x v|, and for a central force r1 x v_half = r0 x v0, so the second
+// kick sees the same h as the first. For one non-spinning hole this is then
+// the symplectic leapfrog for the potential -M h^2 / r^3 (+ Q^2 h^2 / 2r^4),
+// and normalising the direction only reparametrises the ray. The kerr force
+// isn't central and each hole has its own h, so spin or several holes leave
+// an ordinary second order method
+fn next_ray_verlet(in_ray: Ray, step_size: f32) -> Ray {
+    var ray = in_ray;
+
+    let half_direction = ray.direction + 0.5 * step_size * f(ray.position, ray.direction);
+
+    ray.position += half_direction * step_size;
+    ray.direction = normalize(half_direction + 0.5 * step_size * f(ray.position, half_direction));
+
+    return ray;
+}
+
+fn next_ray_euler(in_ray: Ray, step_size: f32) -> Ray {
+    var ray = in_ray;
+
+    ray.direction += f(ray.position, ray.direction) * step_size;
     ray.direction = normalize(ray.direction);
 
     ray.position += ray.direction * step_size;
@@ -479,24 +848,328 @@ fn next_ray_euler(in_ray: Ray, step_size: f32) -> Ray {
     return ray;
 }
 
-fn trace_ray(ray: Ray) -> vec4<f32> {
-    let bh_position = black_hole.position;
-    let bh_radius = black_hole.relativity_radius;
+fn next_ray(rk_state_in: RKState) -> RKState {
+    var rk_state = rk_state_in;
+
+    switch details.integration_method {
+        case INTEGRATION_CASH_KARP: {
+            rk_state = next_ray_cash_karp(rk_state);
+        }
+        case INTEGRATION_RK4: {
+            rk_state.ray = next_ray_rk4(rk_state.ray, rk_state.h);
+        }
+        case INTEGRATION_DORMAND_PRINCE: {
+            rk_state = next_ray_dormand_prince(rk_state);
+        }
+        case INTEGRATION_VERLET: {
+            rk_state.ray = next_ray_verlet(rk_state.ray, rk_state.h);
+        }
+        default: {
+            rk_state.ray = next_ray_euler(rk_state.ray, rk_state.h);
+        }
+    }
+
+    return rk_state;
+}
+
+fn relativity_sphere(index: i32) -> Sphere {
+    return Sphere(black_holes[index].relativity_radius, black_holes[index].position, vec3<f32>(0.0));
+}
 
-    var relativity = false;
+fn inside_relativity(position: vec3<f32>) -> bool {
+    for(var i = 0; i < details.black_hole_count; i++) {
+        if distance(position, black_holes[i].position) < black_holes[i].relativity_radius {
+            return true;
+        }
+    }
+
+    return false;
+}
 
-    if distance(ray.position, bh_position) < bh_radius {
-        relativity = true;
+fn feather_amount(position: vec3<f32>) -> f32 {
+    var linear_mix_amount = 1.0;
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        let bh_radius = black_holes[i].relativity_radius;
+        let feather_width = bh_radius*black_holes[i].feather_amount;
+        let feather_start = bh_radius-feather_width;
+        let dist = distance(position, black_holes[i].position);
+
+        linear_mix_amount = min(linear_mix_amount, clamp((dist-feather_start)/feather_width, 0.0, 1.0));
     }
 
+    return linear_mix_amount;
+}
+
+// translucent shell that gets brighter towards its silhouette
+fn guide_overlay(color: vec3<f32>, render_state_in: RenderState, direction: vec3<f32>) -> RenderState {
+    var render_state = render_state_in;
+
+    let rim = 1.0 - abs(dot(render_state.normal, normalize(direction)));
+
+    render_state.color = color;
+    render_state.opacity = 0.1 + 0.4 * pow(rim, 3.0);
+
+    return render_state;
+}
+
+// shell of the relativity sphere the ray is leaving, positioned at the step
+// that crossed it
+fn relativity_overlay(ray: Ray) -> RenderState {
+    var render_state: RenderState;
+    render_state.hit = false;
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        if black_holes[i].show_relativity_sphere != 0 && distance(ray.position, black_holes[i].position) < black_holes[i].relativity_radius {
+            render_state.normal = normalize(ray.position - black_holes[i].position);
+            render_state.t = 0.0;
+            render_state.hit = true;
+
+            return guide_overlay(RELATIVITY_SPHERE_COLOR, render_state, ray.direction);
+        }
+    }
+
+    return render_state;
+}
+
+fn deflection_lookup(impact_fraction: f32, entry_radius: f32) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_deflection));
+    let radius_fraction = log(entry_radius / DEFLECTION_LUT_MIN_RADIUS) / log(DEFLECTION_LUT_MAX_RADIUS / DEFLECTION_LUT_MIN_RADIUS);
+
+    let p = vec2<f32>(impact_fraction, radius_fraction) * (size - 1.0);
+    let p0 = clamp(floor(p), vec2<f32>(0.0), size - 2.0);
+    let fraction = p - p0;
+    let i0 = vec2<i32>(p0);
+
+    let a = textureLoad(t_deflection, i0, 0);
+    let b = textureLoad(t_deflection, i0 + vec2<i32>(1, 0), 0);
+    let c = textureLoad(t_deflection, i0 + vec2<i32>(0, 1), 0);
+    let d = textureLoad(t_deflection, i0 + vec2<i32>(1, 1), 0);
+
+    return mix(mix(a, b, fraction.x), mix(c, d, fraction.x), fraction.y);
+}
+
+// anything the ray could hit has to stay outside this radius to skip the
+// integration
+fn deflection_clearance(black_hole: BlackHole) -> f32 {
+    var clearance = max(black_hole.outer_radius, details.deflection_clearance * black_hole.photon_sphere_radius);
+
+    if black_hole.volumetric_disk != 0 {
+        clearance = max(clearance, black_hole.outer_radius * (1.0 + 4.0 * black_hole.disk_scale_height));
+    }
+
+    if black_hole.show_jets != 0 {
+        clearance = max(clearance, black_hole.jet_length * (1.0 + tan(0.5 * black_hole.jet_opening_angle)));
+    }
+
+    return clearance;
+}
+
+// tests the root bounds of each visible model against the sphere
+fn any_model_in_sphere(center: vec3<f32>, radius: f32) -> bool {
+    for(var i = 0; i < details.model_count; i++) {
+        if models[i].visible == 0 {
+            continue;
+        }
+
+        let root = models[i].nodes[0];
+        let closest = clamp(center, root.min_corner + models[i].position, root.max_corner + models[i].position);
+
+        if distance(closest, center) < radius {
+            return true;
+        }
+    }
+
+    return false;
+}
+
+// carries a ray that just entered a relativity sphere straight to where it
+// leaves it, only for lone non spinning holes where the lut holds
+fn deflect_ray(index: i32, ray: Ray) -> Deflection {
+    var deflection: Deflection;
+    deflection.valid = false;
+
+    let black_hole = black_holes[index];
+    let entry_radius = black_hole.relativity_radius / black_hole.mass;
+
+    // the table is schwarzschild, spin or charge bend rays differently
+    if details.deflection_lut == 0 || !METRIC_USES_LUT || abs(black_hole.spin) > 1e-4 || black_hole.charge != 0.0 {
+        return deflection;
+    }
+
+    if entry_radius < DEFLECTION_LUT_MIN_RADIUS || entry_radius > DEFLECTION_LUT_MAX_RADIUS {
+        return deflection;
+    }
+
+    // meshes inside the sphere need the curved steps
+    if details.lens_meshes != 0 && any_model_in_sphere(black_hole.position, black_hole.relativity_radius) {
+        return deflection;
+    }
+
+    for(var j = 0; j < details.black_hole_count; j++) {
+        if j != index && distance(black_hole.position, black_holes[j].position) < black_hole.relativity_radius + black_holes[j].relativity_radius {
+            return deflection;
+        }
+    }
+
+    let e1 = normalize(ray.position - black_hole.position);
+    let tangent = ray.direction - dot(ray.direction, e1) * e1;
+
+    if length(tangent) < 1e-6 {
+        return deflection;
+    }
+
+    let e2 = normalize(tangent);
+    let sin_angle = length(tangent);
+    let cos_angle = sqrt(max(1.0 - sin_angle * sin_angle, 0.0));
+
+    let lookup = deflection_lookup(sin_angle, entry_radius);
+
+    if lookup.a < 0.999 || lookup.y * black_hole.mass < deflection_clearance(black_hole) {
+        return deflection;
+    }
+
+    let angle = lookup.x;
+
+    // same angle to the radial on the way out as on the way in
+    let exit_radial = cos(angle) * e1 + sin(angle) * e2;
+    let exit_tangent = -sin(angle) * e1 + cos(angle) * e2;
+
+    deflection.valid = true;
+    deflection.ray.position = black_hole.position + exit_radial * black_hole.relativity_radius * 1.0001;
+    deflection.ray.direction = normalize(cos_angle * exit_radial + sin_angle * exit_tangent);
+    deflection.swept_angle = angle;
+    deflection.closest_position = black_hole.position + (cos(0.5 * angle) * e1 + sin(0.5 * angle) * e2) * lookup.y * black_hole.mass;
+
+    // the disk plane cuts the orbit plane every half turn, the planes of
+    // other holes are ignored here
+    var first_crossing = atan2(-dot(e1, black_hole.normal), dot(e2, black_hole.normal));
+
+    if first_crossing <= 0.0 {
+        first_crossing += PI;
+    }
+
+    deflection.crossings = 0;
+
+    if angle > first_crossing {
+        deflection.crossings = i32(floor((angle - first_crossing) / PI)) + 1;
+    }
+
+    return deflection;
+}
+
+fn wormhole_sphere(index: i32) -> Sphere {
+    return Sphere(wormholes[index].relativity_radius, wormholes[index].position, vec3<f32>(0.0));
+}
+
+fn inside_wormhole(position: vec3<f32>) -> i32 {
+    for(var i = 0; i < details.wormhole_count; i++) {
+        if distance(position, wormholes[i].position) < wormholes[i].relativity_radius {
+            return i;
+        }
+    }
+
+    return -1;
+}
+
+// ellis metric in the orbit plane, l is the proper radial distance and p its
+// rate of change, returns (dl, dp, dphi)
+fn wormhole_derivative(l: f32, p: f32, h: f32, throat_radius: f32) -> vec3<f32> {
+    let r2 = l * l + throat_radius * throat_radius;
+
+    return vec3<f32>(p, h * h * l / (r2 * r2), h / r2);
+}
+
+// integrates a ray from inside the sphere of a wormhole, starting on this side,
+// until it leaves the sphere on either side. Nothing inside the sphere is hit
+fn traverse_wormhole(index: i32, ray: Ray) -> WormholeExit {
+    let wormhole = wormholes[index];
+    let b = wormhole.throat_radius;
+    let exit_radius = wormhole.relativity_radius;
+    let exit_l = sqrt(max(exit_radius * exit_radius - b * b, 0.0));
+
+    let offset = ray.position - wormhole.position;
+    let r = max(length(offset), b);
+    let e1 = normalize(offset);
+    let direction = normalize(ray.direction);
+    let tangent = direction - dot(direction, e1) * e1;
+
+    // any perpendicular will do for a radial ray
+    var e2 = cross(e1, vec3<f32>(0.0, 1.0, 0.0));
+
+    if length(e2) < 1e-3 {
+        e2 = cross(e1, vec3<f32>(1.0, 0.0, 0.0));
+    }
+
+    e2 = normalize(e2);
+
+    if length(tangent) > 1e-6 {
+        e2 = normalize(tangent);
+    }
+
+    // unit speed far away, so p^2 + h^2/r^2 = 1
+    let h = r * length(tangent);
+
+    var state = vec3<f32>(sqrt(max(r * r - b * b, 0.0)), dot(direction, e1), 0.0);
+
+    var exit: WormholeExit;
+    exit.trapped = true;
+
+    for(var i = 0; i < WORMHOLE_MAX_STEPS; i++) {
+        let step = 0.05 * sqrt(state.x * state.x + b * b);
+
+        let k1 = wormhole_derivative(state.x, state.y, h, b);
+        let k2 = wormhole_derivative(state.x + 0.5 * step * k1.x, state.y + 0.5 * step * k1.y, h, b);
+        let k3 = wormhole_derivative(state.x + 0.5 * step * k2.x, state.y + 0.5 * step * k2.y, h, b);
+        let k4 = wormhole_derivative(state.x + step * k3.x, state.y + step * k3.y, h, b);
+
+        state += step / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4);
+
+        if abs(state.x) >= exit_l && state.x * state.y > 0.0 {
+            exit.trapped = false;
+            break;
+        }
+    }
+
+    let angle = state.z;
+    let exit_radial = cos(angle) * e1 + sin(angle) * e2;
+    let exit_tangent = -sin(angle) * e1 + cos(angle) * e2;
+
+    exit.through = state.x < 0.0;
+    exit.swept_angle = angle;
+
+    // the far side is mirrored through the throat, so leaving it outwards
+    // looks the same as leaving this side
+    exit.ray.position = wormhole.position + exit_radial * exit_radius * 1.0001;
+    exit.ray.direction = normalize(abs(state.y) * exit_radial + h / exit_radius * exit_tangent);
+
+    return exit;
+}
+
+fn sample_sky(direction: vec3<f32>, other_side: bool) -> vec3<f32> {
+    let out = cartesian_to_spherical(direction.xzy);
+    let uv = vec2<f32>((out.z + 2.6*PI) / (2.0 * PI), (PI - out.y) / PI) % vec2<f32>(1.0);
+
+    var sky_color: vec3<f32>;
+
+    if other_side {
+        sky_color = textureSampleLevel(t_sky_other, s_sky, uv, 0.0).rgb;
+    } else {
+        sky_color = textureSampleLevel(t_sky, s_sky, uv, 0.0).rgb;
+    }
+
+    return pow(sky_color, vec3<f32>(4.0));
+}
+
+fn trace_ray(ray: Ray) -> TraceResult {
+    var relativity = inside_relativity(ray.position);
+
     let t_max = 1e5;
     let t_min = 1e-8;
 
     var curr_ray = ray;
     var prev_ray = ray;
 
-    let relativity_sphere = Sphere(bh_radius, bh_position, vec3<f32>(0.0));
-
     var color_amount = 1.0;
     var color = vec3<f32>(0.0);
 
@@ -506,73 +1179,221 @@ fn trace_ray(ray: Ray) -> vec4<f32> {
         step_size,
         0.0,
         curr_ray,
+        f(curr_ray.position, curr_ray.direction),
     );
 
-    let ray_distance = distance(ray.position, bh_position);
-
     var hit = false;
 
     var i = 0; 
-    var closeset_to_bh = distance(curr_ray.position, bh_position);
+
+    // direction the ray had when it entered the relativity spheres, the
+    // feathering blends back towards it on the way out
+    var entry_direction = ray.direction;
+    var linear_mix_amount = feather_amount(ray.position);
+
+    // the image order of a hit is the number of times the ray crossed a
+    // disk plane before it, 0 is the primary image
+    var crossings = 0;
+    var swept_angle = 0.0;
+    var first_order = -1;
+
+    var other_side = false;
+
+    // the camera can start inside a wormhole sphere
+    let start_wormhole = inside_wormhole(curr_ray.position);
+
+    if start_wormhole >= 0 && !relativity {
+        let exit = traverse_wormhole(start_wormhole, curr_ray);
+
+        curr_ray = exit.ray;
+        swept_angle += exit.swept_angle;
+        other_side = exit.through;
+
+        if exit.trapped {
+            color_amount = 0.0;
+        }
+
+        if exit.through || exit.trapped {
+            hit = true;
+            i = details.max_iterations;
+        }
+    }
 
     for(; i < details.max_iterations; i++) {
         var closest_render_state: RenderState;
         closest_render_state.t = t_max;
 
+        let curved = relativity;
+
         if relativity {
             prev_ray = curr_ray;
 
-            if details.integration_method == 0 {
-                curr_ray = next_ray_euler(curr_ray, step_size);
-            } else {
-                rk_state = next_ray_rk(rk_state);
-                curr_ray = rk_state.ray;
-                step_size = rk_state.h;
-            }
+            rk_state = next_ray(rk_state);
+            curr_ray = rk_state.ray;
 
-            let curr_distance_to_bh = distance(curr_ray.position, bh_position);
+            // the adaptive methods leave h grown for the next step, the hit
+            // test wants the step that was just taken
+            step_size = distance(prev_ray.position, curr_ray.position);
 
-            if curr_distance_to_bh < closeset_to_bh {
-                closeset_to_bh = curr_distance_to_bh;
-            }
+            linear_mix_amount = min(linear_mix_amount, feather_amount(curr_ray.position));
 
             prev_ray.direction = curr_ray.direction;
 
-            closest_render_state = hit_ray(prev_ray, t_min, step_size, ray_distance, false, true);
+            closest_render_state = hit_ray(prev_ray, t_min, step_size, details.lens_meshes != 0, true);
+
+            var volume_t = step_size;
+
+            if closest_render_state.hit {
+                volume_t = closest_render_state.t;
+            }
 
-            if curr_distance_to_bh > bh_radius {
+            let volume = march_volumes(prev_ray, volume_t);
+
+            if volume.a < 0.9999 || any(volume.rgb > vec3<f32>(0.0)) {
+                if order_visible(crossings) {
+                    color += color_amount * volume.rgb;
+                }
+
+                color_amount *= volume.a;
+                hit = true;
+            }
+
+            if !inside_relativity(curr_ray.position) {
                 relativity = false;
-		
-                let feather_width = bh_radius*black_hole.feather_amount;
-                let feather_start = bh_radius-feather_width;
-                let linear_mix_amount = clamp((closeset_to_bh-feather_start)/feather_width, 0.0, 1.0);
+
+                if !closest_render_state.hit {
+                    closest_render_state = relativity_overlay(prev_ray);
+                }
 
                 let mix_amount = pow(linear_mix_amount, 2.0);
 
-                curr_ray.direction = mix(curr_ray.direction, ray.direction, mix_amount);
+                curr_ray.direction = mix(curr_ray.direction, entry_direction, mix_amount);
             }
         } else {
-            let ray_distance = distance(ray.position, bh_position);
-            let render_state = hit_ray(curr_ray, t_min, t_max, ray_distance, true, false);
-            let hit_sphere_state = hit_sphere(prev_ray, relativity_sphere, t_min, t_max);
+            let render_state = hit_ray(curr_ray, t_min, t_max, true, false);
+
+            var hit_sphere_state: RenderState;
+            hit_sphere_state.t = t_max;
+
+            var hit_sphere_index = 0;
+
+            for(var j = 0; j < details.black_hole_count; j++) {
+                let sphere_state = hit_sphere(curr_ray, relativity_sphere(j), t_min, t_max);
+
+                if sphere_state.hit && sphere_state.t < hit_sphere_state.t {
+                    hit_sphere_state = sphere_state;
+                    hit_sphere_index = j;
+                }
+            }
+
+            var wormhole_t = t_max;
+            var wormhole_index = -1;
+
+            for(var j = 0; j < details.wormhole_count; j++) {
+                let sphere_state = hit_sphere(curr_ray, wormhole_sphere(j), t_min, t_max);
 
-            if !hit_sphere_state.hit && !render_state.hit {
+                if sphere_state.hit && sphere_state.t < wormhole_t {
+                    wormhole_t = sphere_state.t;
+                    wormhole_index = j;
+                }
+            }
+
+            if !hit_sphere_state.hit && !render_state.hit && wormhole_index < 0 {
                 break;
             }
 
-            if hit_sphere_state.hit && hit_sphere_state.t < render_state.t {
+            if wormhole_index >= 0 && wormhole_t < hit_sphere_state.t && wormhole_t < render_state.t {
+                curr_ray.position += curr_ray.direction * wormhole_t;
+
+                let exit = traverse_wormhole(wormhole_index, curr_ray);
+
+                curr_ray = exit.ray;
+                swept_angle += exit.swept_angle;
+
+                if exit.trapped {
+                    color_amount = 0.0;
+                    hit = true;
+                    break;
+                }
+
+                if exit.through {
+                    other_side = true;
+                    hit = true;
+                    break;
+                }
+            } else if hit_sphere_state.hit && hit_sphere_state.t < render_state.t {
                 curr_ray.position += curr_ray.direction * hit_sphere_state.t;
-                relativity = true;
+
+                let deflection = deflect_ray(hit_sphere_index, curr_ray);
+
+                if deflection.valid {
+                    let mix_amount = pow(feather_amount(deflection.closest_position), 2.0);
+                    let entry_ray = curr_ray;
+
+                    curr_ray = deflection.ray;
+                    curr_ray.direction = mix(curr_ray.direction, entry_ray.direction, mix_amount);
+
+                    crossings += deflection.crossings;
+                    swept_angle += deflection.swept_angle;
+
+                    // entry and exit look the same by symmetry
+                    if black_holes[hit_sphere_index].show_relativity_sphere != 0 {
+                        closest_render_state = guide_overlay(RELATIVITY_SPHERE_COLOR, hit_sphere_state, entry_ray.direction);
+                        closest_render_state.opacity = 1.0 - pow(1.0 - closest_render_state.opacity, 2.0);
+                    }
+                } else {
+                    relativity = true;
+
+                    entry_direction = curr_ray.direction;
+                    linear_mix_amount = 1.0;
+                    rk_state.ray = curr_ray;
+                    rk_state.acceleration = f(curr_ray.position, curr_ray.direction);
+
+                    if black_holes[hit_sphere_index].show_relativity_sphere != 0 {
+                        closest_render_state = guide_overlay(RELATIVITY_SPHERE_COLOR, hit_sphere_state, curr_ray.direction);
+                    }
+                }
             } else {
                 closest_render_state = render_state;
+                curr_ray.position += curr_ray.direction * render_state.t;
             }
         }
 
         if closest_render_state.hit {
-            curr_ray.position += prev_ray.direction * closest_render_state.t;
-            color += color_amount * closest_render_state.opacity * clamp(closest_render_state.color, vec3<f32>(0.0), vec3<f32>(1.0));
+            if order_visible(crossings) {
+                color += color_amount * closest_render_state.opacity * clamp(closest_render_state.color, vec3<f32>(0.0), vec3<f32>(1.0));
+            }
+
             color_amount *= 1.0 - closest_render_state.opacity;
             hit = true;
+
+            if first_order < 0 {
+                first_order = crossings;
+            }
+        }
+
+        if curved {
+            var nearest = 0;
+
+            for(var j = 1; j < details.black_hole_count; j++) {
+                if distance(curr_ray.position, black_holes[j].position) < distance(curr_ray.position, black_holes[nearest].position) {
+                    nearest = j;
+                }
+            }
+
+            let prev_offset = prev_ray.position - black_holes[nearest].position;
+            let curr_offset = curr_ray.position - black_holes[nearest].position;
+
+            swept_angle += acos(clamp(dot(normalize(prev_offset), normalize(curr_offset)), -1.0, 1.0));
+
+            for(var j = 0; j < details.black_hole_count; j++) {
+                let prev_height = dot(prev_ray.position - black_holes[j].position, black_holes[j].normal);
+                let curr_height = dot(curr_ray.position - black_holes[j].position, black_holes[j].normal);
+
+                if prev_height * curr_height < 0.0 {
+                    crossings += 1;
+                }
+            }
         }
 
         if color_amount < 0.005 {
@@ -580,22 +1401,57 @@ fn trace_ray(ray: Ray) -> vec4<f32> {
         }
     }
 
+    var result: TraceResult;
+    result.order = vec4<f32>(f32(crossings), swept_angle / PI, f32(first_order), 1.0);
+
     if hit || i <= 5 {
-        if color_amount > 0.001 {
-            let out = cartesian_to_spherical(curr_ray.direction.xzy);
-            let uv = vec2<f32>((out.z + 2.6*PI) / (2.0 * PI), (PI - out.y) / PI) % vec2<f32>(1.0);
-            let sky_color: vec3<f32> = textureSampleLevel(t_sky, s_sky, uv, 0.0).rgb;
-            let miss_color = pow(sky_color, vec3<f32>(4.0));
+        if color_amount > 0.001 && order_visible(crossings) {
+            let miss_color = doppler_shift_color(sample_sky(curr_ray.direction, other_side), sky_shift);
             color += color_amount * miss_color;
         }
 
-        return vec4<f32>(color, 1.0);
+        result.color = vec4<f32>(color, 1.0);
+    } else if !order_visible(crossings) {
+        result.color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    } else {
+        // escaped, the sky pass samples along rgb and reads the shift back
+        // from the negative alpha
+        result.color = vec4<f32>(normalize(curr_ray.direction), -max(sky_shift, 1e-4));
     }
 
-    return vec4<f32>(curr_ray.direction.xyz, 0.0);
+    return result;
 }
 
-fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, total_distance: f32) -> RenderState {
+// the spinning horizon in kerr-schild coordinates, sqrt(r+^2 + a^2) around
+// the equator and r+ at the poles
+fn horizon_equatorial_radius(black_hole: BlackHole) -> f32 {
+    let a = black_hole.spin * black_hole.mass;
+
+    return sqrt(black_hole.horizon_radius * black_hole.horizon_radius + a * a);
+}
+
+// scales the oblate horizon back into a unit sphere, which keeps t since the
+// direction isn't normalised again
+fn hit_horizon(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32) -> RenderState {
+    let axis = black_hole.normal;
+    let polar = black_hole.horizon_radius;
+    let equatorial = horizon_equatorial_radius(black_hole);
+
+    let offset = ray.position - black_hole.position;
+    let position = dot(offset, axis) * axis / polar + (offset - dot(offset, axis) * axis) / equatorial;
+    let direction = dot(ray.direction, axis) * axis / polar + (ray.direction - dot(ray.direction, axis) * axis) / equatorial;
+
+    var render_state = hit_sphere(Ray(position, direction), Sphere(1.0, vec3<f32>(0.0), vec3<f32>(0.0)), t_min, t_max);
+
+    if render_state.hit {
+        let n = render_state.normal;
+        render_state.normal = normalize(dot(n, axis) * axis / polar + (n - dot(n, axis) * axis) / equatorial);
+    }
+
+    return render_state;
+}
+
+fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, observer: vec3<f32>) -> RenderState {
     let torus = Torus2D(
         black_hole.inner_radius,
         black_hole.outer_radius,
@@ -603,11 +1459,14 @@ fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, total
         black_hole.normal,
     );
 
-    let sphere = Sphere(1.0, black_hole.position, vec3<f32>(0.0));
+    var render_state = hit_horizon(ray, black_hole, t_min, t_max);
 
-    var render_state = hit_sphere(ray, sphere, t_min, t_max);
+    var disk_hit = hit_torus2d(ray, torus, t_min, t_max);
 
-    let disk_hit = hit_torus2d(ray, torus, t_min, t_max);
+    // the volumetric disk is marched separately in trace_ray
+    if black_hole.volumetric_disk != 0 {
+        disk_hit.hit = false;
+    }
 
     if disk_hit.hit && disk_hit.t < render_state.t {
         render_state = disk_hit;
@@ -616,10 +1475,12 @@ fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, total
         let dist = distance(black_hole.position, intersection);
 
         let disk_displacement = black_hole.rotation_matrix * vec3<f32>(torus.outer_radius, 1.0, torus.outer_radius);
-        var disk_density = 1.0 - length(intersection / torus.outer_radius);
+        var disk_density = 1.0 - length((intersection - torus.position) / torus.outer_radius);
+
+        let schwarzschild_radius = 2.0 * black_hole.mass;
 
-        disk_density  *= smoothstep(torus.inner_radius, torus.inner_radius + 1.0, dist);
-        disk_density  *= inverseSqrt(dist);
+        disk_density  *= smoothstep(torus.inner_radius, torus.inner_radius + schwarzschild_radius, dist);
+        disk_density  *= inverseSqrt(dist / schwarzschild_radius);
         let optical_depth = pow(30.0 * disk_density, 1.3);
 
         render_state.opacity = clamp(optical_depth*0.2, 0.0, 1.0);
@@ -631,7 +1492,7 @@ fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, total
             let rotated_pos = black_hole.rotation_matrix *  relative_pos;
             let angle = -atan2(rotated_pos.z, rotated_pos.x);
 
-            var uv = vec2<f32>(sin(angle + details.time*black_hole.rotation_speed) * r, cos(angle + details.time*black_hole.rotation_speed) * r);
+            var uv = vec2<f32>(sin(angle + black_hole.disk_phase) * r, cos(angle + black_hole.disk_phase) * r);
             uv = (uv + 1.0) / 2.0;
 
             let disk_color: vec4<f32> = textureSampleLevel(t_disk, s_disk, uv, 0.0);
@@ -640,31 +1501,316 @@ fn hit_black_hole(ray: Ray, black_hole: BlackHole, t_min: f32, t_max: f32, total
             render_state.color *= disk_color.rgb * disk_color.a;
         }
 
-        if black_hole.show_red_shift != 0 {
-            let temp_max = 100000.0;
-            let temp_min = 10000.0;
-            let temp = 15000.0;
-            let y = 1.0 - (temp - temp_min) / (temp_max - temp_min);
-
-            let shiftVector = 0.6 * cross(normalize(intersection), normalize(vec3<f32>(0.0, -1.0, 0.0)));
-            let velocity = dot(ray.direction, shiftVector);
-            let doppler_shift = sqrt((1.0 - velocity) / (1.0 + velocity));
-            let gravitational_shift = sqrt(
-                (1.0 - 2.0 / dist) / 
-                (1.0 - 2.0 / total_distance)
-            );
+        render_state.color *= disk_emission(ray.direction, black_hole, intersection, observer);
+    } else if render_state.hit && black_hole.show_horizon != 0 {
+        let rim = 1.0 - abs(dot(render_state.normal, normalize(ray.direction)));
+        render_state.color = HORIZON_COLOR * pow(rim, 2.0);
+    }
 
-            let shift = pow(clamp(gravitational_shift * doppler_shift, 0.0, 1.0), 2.0);
+    if black_hole.show_photon_sphere != 0 {
+        let photon_sphere = Sphere(black_hole.photon_sphere_radius, black_hole.position, vec3<f32>(0.0));
+        let photon_sphere_state = hit_sphere(ray, photon_sphere, t_min, t_max);
 
-            let shift_color: vec3<f32> = textureSampleLevel(t_temp, s_temp, vec2<f32>(shift, y), 0.0).rgb;
+        if photon_sphere_state.hit && photon_sphere_state.t < render_state.t {
+            render_state = guide_overlay(PHOTON_SPHERE_COLOR, photon_sphere_state, ray.direction);
+        }
+    }
 
-            render_state.color *= shift_color;
+    if black_hole.show_isco != 0 {
+        let isco_width = 0.03 * black_hole.isco_radius;
+        let isco_ring = Torus2D(
+            black_hole.isco_radius - isco_width,
+            black_hole.isco_radius + isco_width,
+            black_hole.position,
+            black_hole.normal,
+        );
+        var isco_state = hit_torus2d(ray, isco_ring, t_min, t_max);
+
+        if isco_state.hit && isco_state.t <= render_state.t {
+            isco_state.color = ISCO_COLOR;
+            isco_state.opacity = 0.8;
+            render_state = isco_state;
         }
     }
 
     return render_state;
 }
 
+// combined gravitational and doppler shift of light from the disk at position,
+// with the gas on keplerian orbits about -normal at v = sqrt(M/r)
+fn disk_redshift(direction: vec3<f32>, black_hole: BlackHole, position: vec3<f32>, observer: vec3<f32>) -> f32 {
+    let offset = position - black_hole.position;
+    let radial = offset - dot(offset, black_hole.normal) * black_hole.normal;
+    let dist = max(length(radial), 1e-4);
+
+    let speed = min(sqrt(black_hole.mass / dist), 0.99);
+    let velocity = speed * cross(radial / dist, black_hole.normal);
+    let gamma = inverseSqrt(1.0 - speed * speed);
+
+    // the light leaves the gas along -direction
+    let doppler_shift = 1.0 / (gamma * (1.0 + dot(direction, velocity)));
+    let gravitational_shift = metric_lapse(position - black_hole.position, black_hole) / metric_lapse(observer, black_hole);
+
+    return gravitational_shift * doppler_shift * observer_shift;
+}
+
+fn disk_shift_color(direction: vec3<f32>, black_hole: BlackHole, position: vec3<f32>, observer: vec3<f32>) -> vec3<f32> {
+    let temp_max = 100000.0;
+    let temp_min = 10000.0;
+    let temp = 15000.0;
+    let y = 1.0 - (temp - temp_min) / (temp_max - temp_min);
+
+    let shift = pow(clamp(disk_redshift(direction, black_hole, position, observer), 0.0, 1.0), 2.0);
+
+    return textureSampleLevel(t_temp, s_temp, vec2<f32>(shift, y), 0.0).rgb;
+}
+
+// novikov-thorne profile, T ~ r^-3/4 (1 - sqrt(r_in/r))^1/4 normalised so
+// disk_temperature is the peak at an accretion rate of 1. T^4 scales with
+// the accretion rate
+fn disk_temperature(black_hole: BlackHole, dist: f32) -> f32 {
+    let x = max(dist / black_hole.inner_radius, 1.0);
+    let profile = pow(x, -0.75) * pow(1.0 - inverseSqrt(x), 0.25) / DISK_PROFILE_PEAK;
+
+    return black_hole.disk_temperature * pow(max(black_hole.accretion_rate, 0.0), 0.25) * profile;
+}
+
+fn cie_lobe(wavelength: f32, mean: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let sigma = select(sigma_high, sigma_low, wavelength < mean);
+    let t = (wavelength - mean) / sigma;
+
+    return exp(-0.5 * t * t);
+}
+
+// planck spectrum integrated against analytic fits of the cie 1931 matching
+// functions, returned as linear srgb with the largest channel at 1
+fn blackbody_color(temperature: f32) -> vec3<f32> {
+    let t = max(temperature, 100.0);
+    var xyz = vec3<f32>(0.0);
+
+    for(var i = 0; i < BLACKBODY_SAMPLES; i++) {
+        let wavelength = 380.0 + 400.0 * (f32(i) + 0.5) / f32(BLACKBODY_SAMPLES);
+        let planck = 1.0 / (pow(wavelength * 1e-3, 5.0) * (exp(1.4388e7 / (wavelength * t)) - 1.0));
+
+        let x = 1.056 * cie_lobe(wavelength, 599.8, 37.9, 31.0) + 0.362 * cie_lobe(wavelength, 442.0, 16.0, 26.7) - 0.065 * cie_lobe(wavelength, 501.1, 20.4, 26.2);
+        let y = 0.821 * cie_lobe(wavelength, 568.8, 46.9, 40.5) + 0.286 * cie_lobe(wavelength, 530.9, 16.3, 31.1);
+        let z = 1.217 * cie_lobe(wavelength, 437.0, 11.8, 36.0) + 0.681 * cie_lobe(wavelength, 459.0, 26.0, 13.8);
+
+        xyz += planck * vec3<f32>(x, y, z);
+    }
+
+    let rgb = max(vec3<f32>(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    ), vec3<f32>(0.0));
+
+    return rgb / max(max(rgb.r, rgb.g), max(rgb.b, 1e-20));
+}
+
+// I_obs = g^n I_emit, n = 3 for line and 4 for continuum emission. The
+// exaggeration scales the exponent to make the asymmetry easier to see
+fn disk_beaming(direction: vec3<f32>, black_hole: BlackHole, position: vec3<f32>, observer: vec3<f32>) -> f32 {
+    if black_hole.beaming_exponent == 0 {
+        return 1.0;
+    }
+
+    let g = max(disk_redshift(direction, black_hole, position, observer), 0.0);
+
+    return pow(g, f32(black_hole.beaming_exponent) * black_hole.beaming_exaggeration);
+}
+
+// colour of the disk material at position, either from the temperature
+// profile or the old fixed temperature lookup
+fn disk_emission(direction: vec3<f32>, black_hole: BlackHole, position: vec3<f32>, observer: vec3<f32>) -> vec3<f32> {
+    let beaming = disk_beaming(direction, black_hole, position, observer);
+
+    if black_hole.temperature_profile == 0 {
+        if black_hole.show_red_shift != 0 {
+            return disk_shift_color(direction, black_hole, position, observer) * beaming;
+        }
+
+        return vec3<f32>(beaming);
+    }
+
+    let temperature = disk_temperature(black_hole, distance(black_hole.position, position));
+
+    var observed_temperature = temperature;
+
+    if black_hole.show_red_shift != 0 {
+        observed_temperature *= disk_redshift(direction, black_hole, position, observer);
+    }
+
+    let intensity = pow(temperature / black_hole.disk_temperature, 4.0);
+
+    return blackbody_color(observed_temperature) * intensity * beaming;
+}
+
+fn hash3(p: vec3<f32>) -> f32 {
+    let q = fract(p * 0.3183099 + vec3<f32>(0.71, 0.113, 0.419)) * 17.0;
+    return fract(q.x * q.y * q.z * (q.x + q.y + q.z));
+}
+
+fn noise3(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let fr = fract(p);
+    let u = fr * fr * (3.0 - 2.0 * fr);
+
+    return mix(
+        mix(
+            mix(hash3(i + vec3<f32>(0.0, 0.0, 0.0)), hash3(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(hash3(i + vec3<f32>(0.0, 1.0, 0.0)), hash3(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y
+        ),
+        mix(
+            mix(hash3(i + vec3<f32>(0.0, 0.0, 1.0)), hash3(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(hash3(i + vec3<f32>(0.0, 1.0, 1.0)), hash3(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y
+        ),
+        u.z
+    );
+}
+
+fn fbm3(p: vec3<f32>) -> f32 {
+    var value = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+
+    for(var i = 0; i < 4; i++) {
+        value += amplitude * noise3(q);
+        q *= 2.03;
+        amplitude *= 0.5;
+    }
+
+    return value;
+}
+
+fn volumetric_disk_density(black_hole: BlackHole, position: vec3<f32>) -> f32 {
+    let schwarzschild_radius = 2.0 * black_hole.mass;
+
+    let relative_pos = position - black_hole.position;
+    let height = dot(relative_pos, black_hole.normal);
+    let radial = relative_pos - black_hole.normal * height;
+    let dist = length(radial);
+
+    let scale_height = max(black_hole.disk_scale_height * dist, 1e-4);
+
+    var density = exp(-0.5 * height * height / (scale_height * scale_height));
+
+    density *= smoothstep(black_hole.inner_radius, black_hole.inner_radius + schwarzschild_radius, dist);
+    density *= 1.0 - smoothstep(0.8 * black_hole.outer_radius, black_hole.outer_radius, dist);
+    density *= inverseSqrt(max(dist / schwarzschild_radius, 1e-4));
+
+    if density < 1e-4 {
+        return 0.0;
+    }
+
+    // the noise turns with the disk, like the texture in the thin disk
+    let rotated_pos = black_hole.rotation_matrix * relative_pos;
+    let angle = -atan2(rotated_pos.z, rotated_pos.x) + black_hole.disk_phase;
+    let noise_pos = vec3<f32>(dist * cos(angle), height, dist * sin(angle)) * black_hole.disk_noise_scale / schwarzschild_radius;
+
+    return density * smoothstep(0.3, 0.8, fbm3(noise_pos));
+}
+
+// bipolar cone along the disk normal, the density falls off across the cone
+// and towards its far end
+fn jet_density(black_hole: BlackHole, position: vec3<f32>) -> f32 {
+    let relative_pos = position - black_hole.position;
+    let height = abs(dot(relative_pos, black_hole.normal));
+    let radial = length(relative_pos - black_hole.normal * dot(relative_pos, black_hole.normal));
+
+    let base_height = 2.0 * black_hole.horizon_radius;
+    let cone_radius = black_hole.horizon_radius + height * tan(0.5 * black_hole.jet_opening_angle);
+
+    var density = exp(-2.0 * radial * radial / (cone_radius * cone_radius));
+
+    density *= smoothstep(black_hole.horizon_radius, base_height, height);
+    density *= 1.0 - smoothstep(0.6 * black_hole.jet_length, black_hole.jet_length, height);
+
+    return density;
+}
+
+// intensity boost of the jet material moving along +-normal, delta^3 for a
+// continuous jet
+fn jet_boost(black_hole: BlackHole, position: vec3<f32>, direction: vec3<f32>) -> f32 {
+    let gamma = max(black_hole.jet_lorentz_factor, 1.0);
+    let beta = sqrt(1.0 - 1.0 / (gamma * gamma));
+
+    let side = sign(dot(position - black_hole.position, black_hole.normal));
+    let cos_theta = dot(side * black_hole.normal, -normalize(direction));
+
+    let doppler = 1.0 / (gamma * (1.0 - beta * cos_theta));
+
+    return pow(doppler * observer_shift, 3.0);
+}
+
+// emission and absorption through the volumetric disks and jets along one
+// step, returned premultiplied with the transmittance in alpha. Disk emission
+// is proportional to its absorption, the jets are optically thin
+fn march_volumes(ray: Ray, t_max: f32) -> vec4<f32> {
+    var transmittance = 1.0;
+    var color = vec3<f32>(0.0);
+
+    let ds = t_max / f32(VOLUME_SAMPLES);
+
+    for(var i = 0; i < details.black_hole_count; i++) {
+        let black_hole = black_holes[i];
+        let schwarzschild_radius = 2.0 * black_hole.mass;
+
+        var march_disk = black_hole.volumetric_disk != 0;
+        var march_jets = black_hole.show_jets != 0;
+
+        let max_height = 4.0 * black_hole.disk_scale_height * black_hole.outer_radius;
+        let start_height = dot(ray.position - black_hole.position, black_hole.normal);
+        let end_height = dot(ray.position + ray.direction * t_max - black_hole.position, black_hole.normal);
+
+        if min(start_height, end_height) > max_height || max(start_height, end_height) < -max_height {
+            march_disk = false;
+        }
+
+        let jet_reach = black_hole.jet_length * (1.0 + tan(0.5 * black_hole.jet_opening_angle)) + t_max;
+
+        if distance(ray.position, black_hole.position) > jet_reach {
+            march_jets = false;
+        }
+
+        if !march_disk && !march_jets {
+            continue;
+        }
+
+        let observer = camera.position - black_hole.position;
+
+        for(var j = 0; j < VOLUME_SAMPLES; j++) {
+            let position = ray.position + ray.direction * (f32(j) + 0.5) * ds;
+
+            if march_jets {
+                let density = jet_density(black_hole, position);
+
+                if density > 0.0 {
+                    let boost = jet_boost(black_hole, position, ray.direction);
+                    color += transmittance * JET_COLOR * black_hole.jet_brightness * boost * density * ds / schwarzschild_radius;
+                }
+            }
+
+            if march_disk {
+                let density = volumetric_disk_density(black_hole, position);
+
+                if density > 0.0 {
+                    let absorbed = 1.0 - exp(-black_hole.disk_density * density * ds / schwarzschild_radius);
+
+                    let sample_color = disk_emission(ray.direction, black_hole, position, observer);
+
+                    color += transmittance * absorbed * sample_color;
+                    transmittance *= 1.0 - absorbed;
+                }
+            }
+        }
+    }
+
+    return vec4<f32>(color, transmittance);
+}
+
 fn hit_torus2d(ray: Ray, torus: Torus2D, t_min: f32, t_max: f32) -> RenderState {
     let normal = torus.normal;
 
@@ -2,6 +2,12 @@
 @group(0) @binding(1) var s_sky: sampler;
 @group(0) @binding(2) var t_sky: texture_2d<f32>;
 @group(0) @binding(3) var t_prev: texture_2d<f32>;
+@group(0) @binding(4) var t_prev_order: texture_2d<f32>;
+@group(0) @binding(5) var<uniform> details: SkyDetails;
+
+struct SkyDetails {
+    show_image_order: i32,
+}
 
 const PI: f32 = 3.1415926;
 
@@ -16,12 +22,17 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
 
     let p = textureLoad(t_prev, screen_pos, 0);
 
-    if p.a == 0.0 {
+    if details.show_image_order != 0 {
+        let order = textureLoad(t_prev_order, screen_pos, 0);
+        textureStore(color_buffer, screen_pos, vec4<f32>(order_color(order), 1.0));
+    } else if p.a < 0.0 {
         let out = cartesian_to_spherical(p.xzy);
         let uv = vec2<f32>((out.z + 2.6*PI) / (2.0 * PI), (PI - out.y) / PI) % vec2<f32>(1.0);
 
         let sky_color: vec3<f32> = textureSampleLevel(t_sky, s_sky, uv.xy, 0.0).rgb;
-        let miss_color = pow(sky_color, vec3<f32>(4.0));
+        // the ray pass stores the camera doppler and gravitational shift as
+        // the negated alpha
+        let miss_color = doppler_shift_color(pow(sky_color, vec3<f32>(4.0)), -p.a);
         let color = miss_color;
         textureStore(color_buffer, screen_pos, vec4<f32>(color, 1.0));
     } else {
@@ -29,6 +40,24 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
     }
 }
 
+fn order_color(order: vec4<f32>) -> vec3<f32> {
+    var n = order.z;
+
+    if n < 0.0 {
+        n = order.x;
+    }
+
+    var palette = array<vec3<f32>, 5>(
+        vec3<f32>(0.1, 0.1, 0.1),
+        vec3<f32>(0.9, 0.3, 0.1),
+        vec3<f32>(0.1, 0.8, 0.3),
+        vec3<f32>(0.2, 0.4, 1.0),
+        vec3<f32>(1.0, 1.0, 1.0),
+    );
+
+    return palette[clamp(i32(n) + 1, 0, 4)];
+}
+
 fn cartesian_to_spherical(cartesian: vec3<f32>) -> vec3<f32> {
     let rho = length(cartesian);
     let theta = atan2(length(cartesian.xy), cartesian.z);
@@ -0,0 +1,34 @@
+// shared by ray.wgsl and sky.wgsl, prepended to both from rust
+
+// treats rgb as samples of a spectrum at 610, 550 and 465nm, each channel
+// sees what was emitted at its wavelength times shift. I_v / v^3 is invariant
+fn doppler_shift_color(color: vec3<f32>, shift: f32) -> vec3<f32> {
+    if abs(shift - 1.0) < 1e-4 {
+        return color;
+    }
+
+    let emitted = vec3<f32>(610.0, 550.0, 465.0) * shift;
+
+    return vec3<f32>(
+        sample_rgb_spectrum(color, emitted.x),
+        sample_rgb_spectrum(color, emitted.y),
+        sample_rgb_spectrum(color, emitted.z),
+    ) * pow(shift, 3.0);
+}
+
+// linear between the samples, fading out 100nm past either end
+fn sample_rgb_spectrum(color: vec3<f32>, wavelength: f32) -> f32 {
+    if wavelength >= 610.0 {
+        return color.r * clamp(1.0 - (wavelength - 610.0) / 100.0, 0.0, 1.0);
+    }
+
+    if wavelength >= 550.0 {
+        return mix(color.g, color.r, (wavelength - 550.0) / 60.0);
+    }
+
+    if wavelength >= 465.0 {
+        return mix(color.b, color.g, (wavelength - 465.0) / 85.0);
+    }
+
+    return color.b * clamp(1.0 - (465.0 - wavelength) / 100.0, 0.0, 1.0);
+}
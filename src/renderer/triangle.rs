@@ -112,6 +112,10 @@ impl Model {
         }
     }
 
+    pub fn ident(&self) -> String {
+        self.ident[..self.ident_size].iter().collect()
+    }
+
     pub fn set_ident(&mut self, ident_str: &str) {
         self.ident_size = 0;
 
@@ -0,0 +1,322 @@
+use std::ops::{Add, Mul, Sub};
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::{blackhole::BlackHoleArrayBuffer, camera::Camera};
+
+// keyframes closer together than this are the same keyframe
+const SAME_TIME: f32 = 1e-3;
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Vector3<f32>,
+    pub forward: Vector3<f32>,
+    pub fov: f32,
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlackHolePose {
+    pub accretion_disk_rotation: Vector3<f32>,
+    pub rotation_speed: f32,
+}
+
+// one pose per black hole, by index. None for a hole that took the index
+// of a removed one after this was keyed
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlackHoleKeyframe {
+    pub time: f32,
+    pub poses: Vec<Option<BlackHolePose>>,
+}
+
+trait Keyframe {
+    fn time(&self) -> f32;
+}
+
+impl Keyframe for CameraKeyframe {
+    fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+impl Keyframe for BlackHoleKeyframe {
+    fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+// the four keyframes around time and how far it is between the middle two,
+// the ends are repeated past the first and last keyframe
+fn segment<K: Keyframe>(keyframes: &[K], time: f32) -> Option<([usize; 4], f32)> {
+    let last = keyframes.len().checked_sub(1)?;
+    let next = keyframes.iter().position(|keyframe| keyframe.time() > time).unwrap_or(last + 1);
+
+    if next == 0 {
+        return Some(([0; 4], 0.0));
+    }
+
+    if next > last {
+        return Some(([last; 4], 0.0));
+    }
+
+    let i1 = next - 1;
+    let i2 = next;
+    let span = keyframes[i2].time() - keyframes[i1].time();
+    let t = if span > 0.0 { (time - keyframes[i1].time()) / span } else { 0.0 };
+
+    Some(([i1.saturating_sub(1), i1, i2, (i2 + 1).min(last)], t))
+}
+
+// uniform catmull-rom through p1 and p2
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+// along the great circle from a to b
+fn slerp_forward(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32> {
+    let a = a.normalize();
+    let b = b.normalize();
+    let cos_angle = a.dot(b).clamp(-1.0, 1.0);
+    let mut towards = b - a * cos_angle;
+
+    if towards.magnitude2() < 1e-10 {
+        if cos_angle > 0.0 {
+            return a;
+        }
+
+        // facing opposite ways every great circle works, turn through up
+        let up = if a.y.abs() < 0.99 { Vector3::unit_y() } else { Vector3::unit_x() };
+        towards = up - a * a.dot(up);
+    }
+
+    let angle = cos_angle.acos() * t;
+
+    (a * angle.cos() + towards.normalize() * angle.sin()).normalize()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    pub camera_keyframes: Vec<CameraKeyframe>,
+    pub black_hole_keyframes: Vec<BlackHoleKeyframe>,
+    pub time: f32,
+    pub looping: bool,
+    #[serde(skip)]
+    pub playing: bool,
+    // set when time is moved by hand so the scene follows while paused
+    #[serde(skip)]
+    pub scrubbed: bool,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            camera_keyframes: Vec::new(),
+            black_hole_keyframes: Vec::new(),
+            time: 0.0,
+            looping: true,
+            playing: false,
+            scrubbed: false,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        let camera_end = self.camera_keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+        let black_hole_end = self.black_hole_keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+
+        camera_end.max(black_hole_end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.camera_keyframes.is_empty() && self.black_hole_keyframes.is_empty()
+    }
+
+    pub fn key_camera(&mut self, camera: &Camera) {
+        let keyframe = CameraKeyframe {
+            time: self.time,
+            position: camera.position,
+            forward: camera.forward,
+            fov: camera.fov,
+        };
+
+        insert_keyframe(&mut self.camera_keyframes, keyframe);
+    }
+
+    pub fn key_black_holes(&mut self, black_holes: &BlackHoleArrayBuffer) {
+        let keyframe = BlackHoleKeyframe {
+            time: self.time,
+            poses: black_holes.iter()
+                .map(|black_hole| Some(BlackHolePose {
+                    accretion_disk_rotation: black_hole.accretion_disk_rotation,
+                    rotation_speed: black_hole.rotation_speed,
+                }))
+                .collect(),
+        };
+
+        insert_keyframe(&mut self.black_hole_keyframes, keyframe);
+    }
+
+    // matches ArrayBuffer::remove, the last of count holes moves into index
+    pub fn remove_black_hole(&mut self, index: usize, count: usize) {
+        if index >= count {
+            return;
+        }
+
+        for keyframe in &mut self.black_hole_keyframes {
+            keyframe.poses.resize(count, None);
+            keyframe.poses.swap_remove(index);
+        }
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration());
+        self.scrubbed = true;
+    }
+
+    pub fn update(&mut self, dt: f32, camera: &mut Camera, black_holes: &mut BlackHoleArrayBuffer) {
+        if self.is_empty() {
+            self.playing = false;
+            return;
+        }
+
+        if self.playing {
+            self.time += dt;
+
+            let duration = self.duration();
+
+            if self.time > duration {
+                if self.looping && duration > 0.0 {
+                    self.time %= duration;
+                } else {
+                    self.time = duration;
+                    self.playing = false;
+                }
+            }
+        } else if !self.scrubbed {
+            // leave the scene to the user while paused
+            return;
+        }
+
+        self.scrubbed = false;
+        self.apply(camera, black_holes);
+    }
+
+    pub fn apply(&self, camera: &mut Camera, black_holes: &mut BlackHoleArrayBuffer) {
+        if let Some(([i0, i1, i2, i3], t)) = segment(&self.camera_keyframes, self.time) {
+            let k = &self.camera_keyframes;
+
+            camera.position = catmull_rom(k[i0].position, k[i1].position, k[i2].position, k[i3].position, t);
+            camera.forward = slerp_forward(k[i1].forward, k[i2].forward, t);
+            camera.fov = catmull_rom(k[i0].fov, k[i1].fov, k[i2].fov, k[i3].fov, t);
+        }
+
+        if let Some(([i0, i1, i2, i3], t)) = segment(&self.black_hole_keyframes, self.time) {
+            let k = &self.black_hole_keyframes;
+
+            for i in 0..black_holes.size() {
+                let poses = [&k[i0], &k[i1], &k[i2], &k[i3]].map(|keyframe| keyframe.poses.get(i).and_then(Option::as_ref));
+
+                // holes added after keying are not animated
+                let [Some(p0), Some(p1), Some(p2), Some(p3)] = poses else {
+                    continue;
+                };
+
+                let black_hole = black_holes.get_mut(i).unwrap();
+
+                black_hole.accretion_disk_rotation = catmull_rom(
+                    p0.accretion_disk_rotation,
+                    p1.accretion_disk_rotation,
+                    p2.accretion_disk_rotation,
+                    p3.accretion_disk_rotation,
+                    t,
+                );
+
+                black_hole.rotation_speed = catmull_rom(p0.rotation_speed, p1.rotation_speed, p2.rotation_speed, p3.rotation_speed, t);
+            }
+        }
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// keeps keyframes sorted, replacing one already at the same time
+fn insert_keyframe<K: Keyframe>(keyframes: &mut Vec<K>, keyframe: K) {
+    let time = keyframe.time();
+
+    if let Some(existing) = keyframes.iter_mut().find(|existing| (existing.time() - time).abs() < SAME_TIME) {
+        *existing = keyframe;
+        return;
+    }
+
+    let index = keyframes.iter().position(|existing| existing.time() > time).unwrap_or(keyframes.len());
+    keyframes.insert(index, keyframe);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::scene::blackhole::BlackHole;
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn forward_follows_the_great_circle() {
+        let a = Vector3::new(1.0, 0.0, 0.0);
+        let b = Vector3::new(0.0, 1.0, 0.0);
+
+        assert_close(slerp_forward(a, b, 0.0), a);
+        assert_close(slerp_forward(a, b, 1.0), b);
+        assert_close(slerp_forward(a, b, 0.5), Vector3::new(1.0, 1.0, 0.0).normalize());
+
+        // stays in the plane of a and b rather than tipping towards +z
+        assert!(slerp_forward(a, b, 0.3).z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn forward_turns_around() {
+        let a = -Vector3::unit_z();
+        let b = Vector3::unit_z();
+        let middle = slerp_forward(a, b, 0.5);
+
+        assert!(middle.x.is_finite() && middle.y.is_finite() && middle.z.is_finite());
+        assert!(middle.dot(a).abs() < 1e-4);
+        assert_close(slerp_forward(a, b, 1.0), b);
+    }
+
+    #[test]
+    fn removing_a_hole_keeps_its_keyframes_with_it() {
+        let mut black_holes = BlackHoleArrayBuffer::new();
+
+        for speed in [1.0, 2.0, 3.0] {
+            black_holes.insert(BlackHole { rotation_speed: speed, ..BlackHole::new() });
+        }
+
+        let mut timeline = Timeline::new();
+        timeline.key_black_holes(&black_holes);
+
+        timeline.remove_black_hole(0, black_holes.size());
+        black_holes.remove(0);
+
+        let poses = &timeline.black_hole_keyframes[0].poses;
+
+        for (i, black_hole) in black_holes.iter().enumerate() {
+            assert_eq!(poses[i].unwrap().rotation_speed, black_hole.rotation_speed);
+        }
+    }
+}
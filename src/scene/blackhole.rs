@@ -1,31 +1,272 @@
 use cgmath::{Euler, InnerSpace, Quaternion, Rad, Rotation, Vector3, Zero};
 
+use crate::renderer::array_buffer::{ArrayBuffer, ArrayBufferUniform};
+
+use super::metric::MetricKind;
+
+pub const MAX_BLACK_HOLES: usize = 4;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Beaming {
+    #[default]
+    Off,
+    // g^3, line emission
+    Line,
+    // g^4, bolometric intensity of continuum emission
+    Continuum,
+}
+
+impl From<Beaming> for String {
+    fn from(value: Beaming) -> Self {
+        match value {
+            Beaming::Off => "Off".into(),
+            Beaming::Line => "Line (g^3)".into(),
+            Beaming::Continuum => "Continuum (g^4)".into(),
+        }
+    }
+}
+
+impl From<Beaming> for i32 {
+    fn from(value: Beaming) -> Self {
+        match value {
+            Beaming::Off => 0,
+            Beaming::Line => 3,
+            Beaming::Continuum => 4,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct BlackHole {
     pub position: Vector3<f32>,
     pub accretion_disk_rotation: Vector3<f32>,
     pub accretion_disk_inner: f32,
     pub accretion_disk_outer: f32,
     pub rotation_speed: f32,
+    // how far the disk has turned, accumulated from rotation_speed so keying
+    // the speed doesn't jump the disk
+    pub disk_phase: f32,
     pub relativity_sphere_radius: f32,
     pub show_disk_texture: i32,
     pub show_red_shift: i32,
     pub feather_amount: f32,
+    pub spin: f32,
+    pub disk_follows_isco: i32,
+    pub mass: f32,
+    pub show_photon_sphere: i32,
+    pub show_isco: i32,
+    pub show_horizon: i32,
+    pub show_relativity_sphere: i32,
+    pub volumetric_disk: i32,
+    pub disk_scale_height: f32,
+    pub disk_density: f32,
+    pub disk_noise_scale: f32,
+    pub show_jets: i32,
+    pub jet_length: f32,
+    pub jet_opening_angle: f32,
+    pub jet_brightness: f32,
+    pub jet_lorentz_factor: f32,
+    pub temperature_profile: i32,
+    pub accretion_rate: f32,
+    pub disk_temperature: f32,
+    pub beaming: Beaming,
+    pub beaming_exaggeration: f32,
+    // Q/M, reissner-nordstrom with no spin and kerr-newman with
+    pub charge: f32,
 }
 
 impl BlackHole {
     pub fn new() -> Self {
+        // geometric units (G = c = 1), the default hole has its horizon at r = 1
+        Self::with_mass(0.5)
+    }
+
+    pub fn with_mass(mass: f32) -> Self {
         Self {
             position: Vector3::zero(),
             accretion_disk_rotation: Vector3::new(0.15, 0.0, 0.25),
-            accretion_disk_inner: 2.0,
-            accretion_disk_outer: 10.0,
+            accretion_disk_inner: 4.0 * mass,
+            accretion_disk_outer: 20.0 * mass,
             rotation_speed: 1.0,
-            relativity_sphere_radius: 20.0,
+            disk_phase: 0.0,
+            relativity_sphere_radius: 40.0 * mass,
             show_disk_texture: 1,
             show_red_shift: 1,
-            feather_amount: 0.3
+            feather_amount: 0.3,
+            spin: 0.0,
+            disk_follows_isco: 0,
+            mass,
+            show_photon_sphere: 0,
+            show_isco: 0,
+            show_horizon: 0,
+            show_relativity_sphere: 0,
+            volumetric_disk: 0,
+            disk_scale_height: 0.05,
+            disk_density: 2.0,
+            disk_noise_scale: 1.0,
+            show_jets: 0,
+            jet_length: 30.0 * mass,
+            jet_opening_angle: 0.15,
+            jet_brightness: 1.0,
+            jet_lorentz_factor: 3.0,
+            temperature_profile: 1,
+            accretion_rate: 1.0,
+            disk_temperature: 15000.0,
+            beaming: Beaming::default(),
+            beaming_exaggeration: 1.0,
+            charge: 0.0,
+        }
+    }
+
+    // keeps the disk and relativity sphere at the same size relative to the hole
+    pub fn set_mass(&mut self, mass: f32) {
+        let scale = mass / self.mass;
+
+        self.accretion_disk_inner *= scale;
+        self.accretion_disk_outer *= scale;
+        self.relativity_sphere_radius *= scale;
+        self.jet_length *= scale;
+        self.mass = mass;
+    }
+
+    // the hole as the metric sees it, so the horizon, isco and photon sphere
+    // only use the spin and charge that actually bend the light
+    pub fn for_metric(&self, metric: MetricKind) -> Self {
+        Self {
+            spin: if metric.uses_spin() { self.spin } else { 0.0 },
+            charge: if metric.uses_charge() { self.charge } else { 0.0 },
+            ..*self
+        }
+    }
+
+    pub fn schwarzschild_radius(&self) -> f32 {
+        2.0 * self.mass
+    }
+
+    pub fn horizon_radius(&self) -> f32 {
+        self.mass * (1.0 + (1.0 - self.spin * self.spin - self.charge * self.charge).max(0.0).sqrt())
+    }
+
+    // charge in geometric units
+    pub fn charge_radius(&self) -> f32 {
+        self.charge * self.mass
+    }
+
+    // spin is signed along the disk's angular momentum, so a negative spin
+    // gives the retrograde isco
+    pub fn isco_radius(&self) -> f32 {
+        if self.charge != 0.0 {
+            return self.kerr_newman_isco_radius();
+        }
+
+        let a = self.spin.clamp(-1.0, 1.0);
+        let a_abs = a.abs();
+
+        let z1 = 1.0 + (1.0 - a * a).cbrt() * ((1.0 + a_abs).cbrt() + (1.0 - a_abs).cbrt());
+        let z2 = (3.0 * a * a + z1 * z1).sqrt();
+        let root = ((3.0 - z1) * (3.0 + z1 + 2.0 * z2)).max(0.0).sqrt();
+
+        if a >= 0.0 {
+            self.mass * (3.0 + z2 - root)
+        } else {
+            self.mass * (3.0 + z2 + root)
+        }
+    }
+
+    // the energy of circular orbits falls going in until the isco and rises
+    // again towards the photon orbit, so the isco is where dE/dr crosses zero.
+    // Reduces to the kerr isco without charge and the reissner-nordstrom one
+    // without spin
+    fn kerr_newman_isco_radius(&self) -> f32 {
+        let m = self.mass as f64;
+        let q2 = (self.charge_radius() * self.charge_radius()) as f64;
+        let a = (self.spin.clamp(-1.0, 1.0) * self.mass) as f64;
+
+        let energy = |r: f64| {
+            let root = (m * r - q2).max(0.0).sqrt();
+
+            (r * r - 2.0 * m * r + q2 + a * root) / (r * (r * r - 3.0 * m * r + 2.0 * q2 + 2.0 * a * root).max(1e-12).sqrt())
+        };
+
+        let h = 1e-4 * m;
+        let slope = |r: f32| ((energy(r as f64 + h) - energy(r as f64 - h)) / (2.0 * h)) as f32;
+
+        bisect(slope, self.photon_sphere_radius(), 9.0 * self.mass)
+    }
+
+    // equatorial photon orbit going the same way as the disk, 3M without spin
+    // or charge
+    pub fn photon_sphere_radius(&self) -> f32 {
+        let a = self.spin.clamp(-1.0, 1.0);
+
+        if self.charge == 0.0 {
+            return 2.0 * self.mass * (1.0 + ((2.0 / 3.0) * (-a).acos()).cos());
+        }
+
+        // kerr-newman, r^2 - 3Mr + 2Q^2 + 2a sqrt(Mr - Q^2) = 0
+        let m = self.mass;
+        let q2 = self.charge_radius() * self.charge_radius();
+
+        let orbit = |r: f32| r * r - 3.0 * m * r + 2.0 * q2 + 2.0 * a * m * (m * r - q2).max(0.0).sqrt();
+
+        bisect(orbit, self.horizon_radius(), 5.0 * m)
+    }
+
+    // equatorial distance from the centre in the kerr-schild coordinates the
+    // rays are traced in, where the boyer-lindquist radius r sits at
+    // sqrt(r^2 + a^2)
+    pub fn kerr_schild_radius(&self, r: f32) -> f32 {
+        let a = self.spin * self.mass;
+
+        (r * r + a * a).sqrt()
+    }
+
+    pub fn disk_inner_radius(&self) -> f32 {
+        if self.disk_follows_isco != 0 {
+            self.kerr_schild_radius(self.isco_radius())
+        } else {
+            self.accretion_disk_inner
         }
     }
+
+    // the disk orbits about -normal, so that is the prograde spin axis
+    pub fn angular_momentum(&self) -> Vector3<f32> {
+        -self.disk_normal() * self.spin * self.mass * self.mass
+    }
+
+    pub fn disk_basis(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let q_rotate = Quaternion::from(Euler::new(
+            Rad(self.accretion_disk_rotation.x),
+            Rad(self.accretion_disk_rotation.y),
+            Rad(self.accretion_disk_rotation.z),
+        ));
+
+        let up_vector = q_rotate.rotate_vector(Vector3::new(0.0, -1.0, 0.0)).normalize();
+        let right_vector = Vector3::new(0.0, 0.0, 1.0).cross(up_vector);
+        let forward_vector = right_vector.cross(up_vector);
+
+        (right_vector, up_vector, forward_vector)
+    }
+
+    pub fn disk_normal(&self) -> Vector3<f32> {
+        self.disk_basis().1
+    }
+}
+
+// root of an increasing function between low and high
+fn bisect(f: impl Fn(f32) -> f32, mut low: f32, mut high: f32) -> f32 {
+    for _ in 0..40 {
+        let mid = 0.5 * (low + high);
+
+        if f(mid) < 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    0.5 * (low + high)
 }
 
 impl Default for BlackHole {
@@ -39,7 +280,7 @@ impl Default for BlackHole {
 pub struct BlackHoleUniform {
     accretion_disk_inner: f32,
     accretion_disk_outer: f32,
-    rotation_speed: f32,
+    disk_phase: f32,
     relativity_sphere_radius: f32,
     position: [f32; 3],
     show_disk_texture: i32,
@@ -47,7 +288,31 @@ pub struct BlackHoleUniform {
     show_red_shift: i32,
     rotation_matrix: [f32; 12],
     feather_amount: f32,
-    pad: [i32; 8],
+    horizon_radius: f32,
+    spin: f32,
+    mass: f32,
+    angular_momentum: [f32; 3],
+    photon_sphere_radius: f32,
+    isco_radius: f32,
+    show_photon_sphere: i32,
+    show_isco: i32,
+    show_horizon: i32,
+    show_relativity_sphere: i32,
+    volumetric_disk: i32,
+    disk_scale_height: f32,
+    disk_density: f32,
+    disk_noise_scale: f32,
+    show_jets: i32,
+    jet_length: f32,
+    jet_opening_angle: f32,
+    jet_brightness: f32,
+    jet_lorentz_factor: f32,
+    temperature_profile: i32,
+    accretion_rate: f32,
+    disk_temperature: f32,
+    beaming_exponent: i32,
+    beaming_exaggeration: f32,
+    charge: f32,
 }
 
 impl BlackHoleUniform {
@@ -55,7 +320,7 @@ impl BlackHoleUniform {
         Self {
             accretion_disk_inner: 0.0,
             accretion_disk_outer: 0.0,
-            rotation_speed: 0.0,
+            disk_phase: 0.0,
             relativity_sphere_radius: 0.0,
             position: [0.0; 3],
             show_disk_texture: 1,
@@ -63,29 +328,78 @@ impl BlackHoleUniform {
             show_red_shift: 1,
             rotation_matrix: [0.0; 12],
             feather_amount: 0.0,
-            pad: [0; 8],
+            horizon_radius: 0.0,
+            spin: 0.0,
+            mass: 0.0,
+            angular_momentum: [0.0; 3],
+            photon_sphere_radius: 0.0,
+            isco_radius: 0.0,
+            show_photon_sphere: 0,
+            show_isco: 0,
+            show_horizon: 0,
+            show_relativity_sphere: 0,
+            volumetric_disk: 0,
+            disk_scale_height: 0.0,
+            disk_density: 0.0,
+            disk_noise_scale: 0.0,
+            show_jets: 0,
+            jet_length: 0.0,
+            jet_opening_angle: 0.0,
+            jet_brightness: 0.0,
+            jet_lorentz_factor: 0.0,
+            temperature_profile: 1,
+            accretion_rate: 0.0,
+            disk_temperature: 0.0,
+            beaming_exponent: 0,
+            beaming_exaggeration: 0.0,
+            charge: 0.0,
         }
     }
+}
 
-    pub fn update(&mut self, black_hole: &BlackHole) {
+impl Default for BlackHoleUniform { fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayBufferUniform<BlackHole> for BlackHoleUniform {
+    fn update(&mut self, black_hole: &BlackHole) {
         self.position = black_hole.position.into();
-        self.accretion_disk_inner = black_hole.accretion_disk_inner;
+        self.accretion_disk_inner = black_hole.disk_inner_radius();
         self.accretion_disk_outer = black_hole.accretion_disk_outer;
-        self.rotation_speed = black_hole.rotation_speed;
+        self.disk_phase = black_hole.disk_phase;
         self.relativity_sphere_radius = black_hole.relativity_sphere_radius;
         self.show_disk_texture = black_hole.show_disk_texture;
         self.show_red_shift = black_hole.show_red_shift;
         self.feather_amount = black_hole.feather_amount;
+        self.horizon_radius = black_hole.horizon_radius();
+        self.spin = black_hole.spin;
+        self.mass = black_hole.mass;
+        self.angular_momentum = black_hole.angular_momentum().into();
+        self.photon_sphere_radius = black_hole.kerr_schild_radius(black_hole.photon_sphere_radius());
+        self.isco_radius = black_hole.kerr_schild_radius(black_hole.isco_radius());
+        self.show_photon_sphere = black_hole.show_photon_sphere;
+        self.show_isco = black_hole.show_isco;
+        self.show_horizon = black_hole.show_horizon;
+        self.show_relativity_sphere = black_hole.show_relativity_sphere;
+        self.volumetric_disk = black_hole.volumetric_disk;
+        self.disk_scale_height = black_hole.disk_scale_height;
+        self.disk_density = black_hole.disk_density;
+        self.disk_noise_scale = black_hole.disk_noise_scale;
+        self.show_jets = black_hole.show_jets;
+        // jets are only marched inside the relativity sphere
+        self.jet_length = black_hole.jet_length.min(black_hole.relativity_sphere_radius);
+        self.jet_opening_angle = black_hole.jet_opening_angle;
+        self.jet_brightness = black_hole.jet_brightness;
+        self.jet_lorentz_factor = black_hole.jet_lorentz_factor;
+        self.temperature_profile = black_hole.temperature_profile;
+        self.accretion_rate = black_hole.accretion_rate;
+        self.disk_temperature = black_hole.disk_temperature;
+        self.beaming_exponent = black_hole.beaming.into();
+        self.beaming_exaggeration = black_hole.beaming_exaggeration;
+        self.charge = black_hole.charge_radius();
 
-        let q_rotate = Quaternion::from(Euler::new(
-            Rad(black_hole.accretion_disk_rotation.x),
-            Rad(black_hole.accretion_disk_rotation.y),
-            Rad(black_hole.accretion_disk_rotation.z),
-        ));
-
-        let up_vector = q_rotate.rotate_vector(Vector3::new(0.0, -1.0, 0.0)).normalize();
-        let right_vector = Vector3::new(0.0, 0.0, 1.0).cross(up_vector);
-        let forward_vector = right_vector.cross(up_vector);
+        let (right_vector, up_vector, forward_vector) = black_hole.disk_basis();
 
         self.rotation_matrix = [
             right_vector.x, right_vector.y, right_vector.z, 0.0,
@@ -97,8 +411,60 @@ impl BlackHoleUniform {
     }
 }
 
-impl Default for BlackHoleUniform { fn default() -> Self {
-        Self::new()
+pub type BlackHoleArrayBuffer = ArrayBuffer<MAX_BLACK_HOLES, BlackHole, BlackHoleUniform>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hole(spin: f32, charge: f32) -> BlackHole {
+        BlackHole { spin, charge, ..BlackHole::with_mass(1.0) }
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() < tolerance, "{} vs {}", actual, expected);
+    }
+
+    #[test]
+    fn bisect_finds_root() {
+        assert_close(bisect(|x| x * x - 2.0, 0.0, 2.0), 2.0f32.sqrt(), 1e-5);
     }
-}
 
+    #[test]
+    fn isco_limits() {
+        assert_close(hole(0.0, 0.0).isco_radius(), 6.0, 1e-4);
+        assert_close(hole(1.0, 0.0).isco_radius(), 1.0, 1e-3);
+        assert_close(hole(-1.0, 0.0).isco_radius(), 9.0, 1e-3);
+
+        // reissner-nordstrom, extremal at Q = M
+        assert_close(hole(0.0, 1.0).isco_radius(), 4.0, 1e-2);
+    }
+
+    #[test]
+    fn shape_follows_the_metric() {
+        let black_hole = hole(0.6, 0.5);
+        let schwarzschild = black_hole.for_metric(MetricKind::Schwarzschild);
+
+        assert_close(schwarzschild.horizon_radius(), 2.0, 1e-6);
+        assert_close(schwarzschild.photon_sphere_radius(), 3.0, 1e-4);
+        assert_close(schwarzschild.isco_radius(), 6.0, 1e-4);
+
+        assert_eq!(black_hole.for_metric(MetricKind::Kerr).charge, 0.0);
+        assert_eq!(black_hole.for_metric(MetricKind::ReissnerNordstrom).spin, 0.0);
+        assert_close(black_hole.for_metric(MetricKind::KerrNewman).horizon_radius(), black_hole.horizon_radius(), 1e-6);
+    }
+
+    #[test]
+    fn charged_isco_follows_spin() {
+        for spin in [-0.9, -0.5, 0.5, 0.9] {
+            let kerr = hole(spin, 0.0).isco_radius();
+
+            assert_close(hole(spin, 0.01).isco_radius(), kerr, 1e-2);
+        }
+
+        // a small charge pulls the isco in a little
+        let charged = hole(0.0, 0.1).isco_radius();
+
+        assert!(charged < 6.0 && charged > 5.9, "{}", charged);
+    }
+}
@@ -1,9 +1,45 @@
-use cgmath::{InnerSpace, Quaternion, Rad, Rotation, Rotation3, Vector3};
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation, Rotation3, Vector3, Zero};
+
+use super::{blackhole::BlackHole, timelike::TimelikeGeodesic};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ObserverMotion {
+    #[default]
+    Static,
+    // velocity is set by hand
+    Moving,
+    CircularOrbit,
+    FreeFall,
+    // integrates a timelike geodesic instead of the wasd controls
+    Geodesic,
+}
+
+impl From<ObserverMotion> for String {
+    fn from(value: ObserverMotion) -> Self {
+        match value {
+            ObserverMotion::Static => "Static".into(),
+            ObserverMotion::Moving => "Moving".into(),
+            ObserverMotion::CircularOrbit => "Circular Orbit".into(),
+            ObserverMotion::FreeFall => "Radial Free Fall".into(),
+            ObserverMotion::Geodesic => "Geodesic".into(),
+        }
+    }
+}
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct Camera {
     pub position: Vector3<f32>,
     pub forward: Vector3<f32>,
     pub fov: f32,
+    // fraction of c, as measured by a static observer at the same place
+    pub velocity: Vector3<f32>,
+    pub motion: ObserverMotion,
+    // geometric time units that pass per second while orbiting or falling
+    pub motion_speed: f32,
+    pub aberration: i32,
+    pub doppler: i32,
+    pub geodesic: TimelikeGeodesic,
 }
 
 impl Camera {
@@ -12,6 +48,12 @@ impl Camera {
             position: Vector3::new(0.0, 0.0, -19.0),
             forward: Vector3::new(0.0, 0.0, 1.0),
             fov: 1.0,
+            velocity: Vector3::zero(),
+            motion: ObserverMotion::default(),
+            motion_speed: 5.0,
+            aberration: 1,
+            doppler: 1,
+            geodesic: TimelikeGeodesic::new(),
         }
     }
 
@@ -34,6 +76,75 @@ impl Camera {
         self.forward = q.rotate_vector(self.forward);
     }
 
+    // orbits and falls use the schwarzschild solution of the nearest hole,
+    // spin is ignored
+    pub fn update_motion(&mut self, black_hole: &BlackHole, dt: f32) {
+        let mass = black_hole.mass;
+        let offset = self.position - black_hole.position;
+
+        // picking the geodesic mode again launches from where the camera is
+        if self.motion != ObserverMotion::Geodesic {
+            self.geodesic.reset();
+        }
+
+        match self.motion {
+            ObserverMotion::Static => {
+                self.velocity = Vector3::zero();
+            },
+            ObserverMotion::Moving => {},
+            ObserverMotion::Geodesic => {
+                let proper_time_step = (dt * self.motion_speed) as f64;
+                self.geodesic.update(&mut self.position, &mut self.velocity, black_hole, proper_time_step);
+            },
+            ObserverMotion::CircularOrbit => {
+                let normal = black_hole.disk_normal();
+                let radial = offset - normal * offset.dot(normal);
+                let radius = radial.magnitude();
+
+                if radius < 1e-4 {
+                    return;
+                }
+
+                // prograde, the disk orbits about -normal
+                let tangent = (-normal).cross(radial).normalize();
+
+                // reaches c at the photon sphere
+                let speed = if radius > 3.0 * mass {
+                    (mass / (radius - 2.0 * mass)).sqrt().min(0.99)
+                } else {
+                    0.99
+                };
+
+                self.velocity = tangent * speed;
+
+                // the coordinate angular velocity is keplerian
+                let angle = (mass / radius.powi(3)).sqrt() * dt * self.motion_speed;
+                let rotation = Quaternion::from_axis_angle(-normal, Rad(angle));
+
+                self.position = black_hole.position + rotation.rotate_vector(offset);
+            },
+            ObserverMotion::FreeFall => {
+                let radius = offset.magnitude();
+                let stop_radius = 1.01 * black_hole.horizon_radius();
+
+                if radius < 1e-4 {
+                    return;
+                }
+
+                // dropped from rest at infinity
+                let speed = (2.0 * mass / radius).sqrt().min(0.99);
+                let inward = -offset / radius;
+
+                self.velocity = inward * speed;
+
+                // seen from far away the fall freezes at the horizon
+                let dr = (1.0 - 2.0 * mass / radius).max(0.0) * speed * dt * self.motion_speed;
+
+                self.position += inward * dr.min((radius - stop_radius).max(0.0));
+            },
+        }
+    }
+
     pub fn set_forward(&mut self, forward: Vector3<f32>) {
         self.forward = forward.normalize();
     }
@@ -70,6 +181,10 @@ pub struct CameraUniform {
     _padding: u32,
     forward: [f32; 3],
     fov: f32,
+    velocity: [f32; 3],
+    aberration: i32,
+    doppler: i32,
+    _padding2: [u32; 3],
 }
 
 impl CameraUniform {
@@ -78,7 +193,11 @@ impl CameraUniform {
             position: [0.0; 3],
             _padding: 0,
             forward: [0.0; 3],
-            fov: 0.0 
+            fov: 0.0,
+            velocity: [0.0; 3],
+            aberration: 0,
+            doppler: 0,
+            _padding2: [0; 3],
         }
     }
 
@@ -86,6 +205,9 @@ impl CameraUniform {
         self.position = camera.position.into();
         self.forward = camera.forward.into();
         self.fov = camera.fov;
+        self.velocity = camera.velocity.into();
+        self.aberration = camera.aberration;
+        self.doppler = camera.doppler;
     }
 }
 
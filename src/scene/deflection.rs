@@ -0,0 +1,132 @@
+use cgmath::{InnerSpace, Vector3};
+
+use super::{blackhole::BlackHole, geodesic::{self, Ray}, metric::Schwarzschild};
+
+// impact parameter as a fraction of the entry radius, 0 is a radial ray and
+// 1 grazes the relativity sphere
+pub const DEFLECTION_LUT_IMPACT_SAMPLES: u32 = 128;
+// entry radius in units of M, spaced logarithmically
+pub const DEFLECTION_LUT_RADIUS_SAMPLES: u32 = 32;
+pub const DEFLECTION_LUT_MIN_RADIUS: f64 = 5.0;
+pub const DEFLECTION_LUT_MAX_RADIUS: f64 = 500.0;
+
+const STEP_FRACTION: f64 = 0.02;
+const MAX_STEPS: usize = 20000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Deflection {
+    // angle swept around the hole between entry and exit
+    pub swept_angle: f64,
+    pub min_radius: f64,
+    pub captured: bool,
+}
+
+pub fn entry_radius(index: u32) -> f64 {
+    let x = index as f64 / (DEFLECTION_LUT_RADIUS_SAMPLES - 1) as f64;
+
+    DEFLECTION_LUT_MIN_RADIUS * (DEFLECTION_LUT_MAX_RADIUS / DEFLECTION_LUT_MIN_RADIUS).powf(x)
+}
+
+pub fn impact_fraction(index: u32) -> f64 {
+    index as f64 / (DEFLECTION_LUT_IMPACT_SAMPLES - 1) as f64
+}
+
+// Schwarzschild only, a ray entering a sphere of radius r (in units of M)
+// leaves it again with the same angle to the radial by symmetry, so the
+// swept angle is all that is needed to place it
+pub fn deflection(impact_fraction: f64, entry_radius: f64) -> Deflection {
+    let black_hole = BlackHole::with_mass(1.0);
+    let black_holes = [black_hole];
+
+    let horizon = black_hole.horizon_radius() as f64;
+
+    let sin_angle = impact_fraction.clamp(0.0, 1.0);
+    let cos_angle = (1.0 - sin_angle * sin_angle).sqrt();
+
+    let mut ray = Ray::new(
+        Vector3::new(entry_radius, 0.0, 0.0),
+        Vector3::new(-cos_angle, sin_angle, 0.0),
+    );
+
+    let mut swept_angle = 0.0;
+    let mut min_radius = entry_radius;
+
+    for _ in 0..MAX_STEPS {
+        let prev_ray = ray;
+        let prev_radius = prev_ray.position.magnitude();
+
+        ray = geodesic::next_ray_rk4(ray, STEP_FRACTION * prev_radius, &black_holes, &Schwarzschild);
+
+        let radius = ray.position.magnitude();
+        let step_angle = prev_ray.position.cross(ray.position).z
+            .atan2(prev_ray.position.dot(ray.position));
+
+        if radius <= horizon {
+            return Deflection { swept_angle, min_radius: 0.0, captured: true };
+        }
+
+        if radius >= entry_radius {
+            let fraction = (entry_radius - prev_radius) / (radius - prev_radius);
+
+            return Deflection {
+                swept_angle: swept_angle + fraction * step_angle,
+                min_radius,
+                captured: false,
+            };
+        }
+
+        swept_angle += step_angle;
+        min_radius = min_radius.min(radius);
+    }
+
+    // orbiting for this long means it is as good as captured
+    Deflection { swept_angle, min_radius: 0.0, captured: true }
+}
+
+// rgba32float texels of (swept angle, minimum radius / M, 0, valid), rows
+// are entry radii
+pub fn generate() -> Vec<[f32; 4]> {
+    let mut data = Vec::with_capacity((DEFLECTION_LUT_IMPACT_SAMPLES * DEFLECTION_LUT_RADIUS_SAMPLES) as usize);
+
+    for y in 0..DEFLECTION_LUT_RADIUS_SAMPLES {
+        let radius = entry_radius(y);
+
+        for x in 0..DEFLECTION_LUT_IMPACT_SAMPLES {
+            let result = deflection(impact_fraction(x), radius);
+            let valid = if result.captured { 0.0 } else { 1.0 };
+
+            data.push([result.swept_angle as f32, result.min_radius as f32, 0.0, valid]);
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn distant_rays_deflect_by_4m_over_b() {
+        for (fraction, radius) in [(0.5, 500.0), (0.9, 500.0), (0.5, 200.0)] {
+            let result = deflection(fraction, radius);
+            let angle = result.swept_angle - (PI - 2.0 * f64::asin(fraction));
+
+            // the 3M h^2 / r^4 pull integrated along the straight chord, this
+            // tends to 4M/b as the sphere grows
+            let b = fraction * radius;
+            let expected = 2.0 / b * (1.0 - fraction * fraction).sqrt() * (2.0 + fraction * fraction);
+
+            assert!(!result.captured);
+            assert!((angle - expected).abs() < 0.05 * expected, "f {} r {}: {} vs {}", fraction, radius, angle, expected);
+        }
+    }
+
+    #[test]
+    fn radial_rays_are_captured() {
+        assert!(deflection(0.0, DEFLECTION_LUT_MIN_RADIUS).captured);
+        assert!(deflection(0.0, DEFLECTION_LUT_MAX_RADIUS).captured);
+    }
+}
@@ -0,0 +1,736 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::renderer::pipelines::ray_pipeline::{IntegrationMethod, RayDetails};
+
+use super::{blackhole::BlackHole, metric::Metric};
+
+// CPU twin of the ray integrators in ray.wgsl. Everything here is kept step
+// for step with the shader (in f64) so the two can be compared directly.
+
+const A_21: f64 = 1.0/5.0;
+
+const A_31: f64 = 3.0/40.0;
+const A_32: f64 = 9.0/40.0;
+
+const A_41: f64 = 3.0/10.0;
+const A_42: f64 = -9.0/10.0;
+const A_43: f64 = 6.0/5.0;
+
+const A_51: f64 = -11.0/54.0;
+const A_52: f64 = 5.0/2.0;
+const A_53: f64 = -70.0/27.0;
+const A_54: f64 = 35.0/27.0;
+
+const A_61: f64 = 1631.0/55296.0;
+const A_62: f64 = 175.0/512.0;
+const A_63: f64 = 575.0/13824.0;
+const A_64: f64 = 44275.0/110592.0;
+const A_65: f64 = 253.0/4096.0;
+
+const B_1: f64 = 37.0/378.0;
+const B_2: f64 = 0.0;
+const B_3: f64 = 250.0/621.0;
+const B_4: f64 = 125.0/594.0;
+const B_5: f64 = 0.0;
+const B_6: f64 = 512.0/1771.0;
+
+const B_A_1: f64 = 2825.0/27648.0;
+const B_A_2: f64 = 0.0;
+const B_A_3: f64 = 18575.0/48384.0;
+const B_A_4: f64 = 13525.0/55296.0;
+const B_A_5: f64 = 277.0/14336.0;
+const B_A_6: f64 = 1.0/4.0;
+
+const DP_A_21: f64 = 1.0/5.0;
+
+const DP_A_31: f64 = 3.0/40.0;
+const DP_A_32: f64 = 9.0/40.0;
+
+const DP_A_41: f64 = 44.0/45.0;
+const DP_A_42: f64 = -56.0/15.0;
+const DP_A_43: f64 = 32.0/9.0;
+
+const DP_A_51: f64 = 19372.0/6561.0;
+const DP_A_52: f64 = -25360.0/2187.0;
+const DP_A_53: f64 = 64448.0/6561.0;
+const DP_A_54: f64 = -212.0/729.0;
+
+const DP_A_61: f64 = 9017.0/3168.0;
+const DP_A_62: f64 = -355.0/33.0;
+const DP_A_63: f64 = 46732.0/5247.0;
+const DP_A_64: f64 = 49.0/176.0;
+const DP_A_65: f64 = -5103.0/18656.0;
+
+const DP_A_71: f64 = 35.0/384.0;
+const DP_A_73: f64 = 500.0/1113.0;
+const DP_A_74: f64 = 125.0/192.0;
+const DP_A_75: f64 = -2187.0/6784.0;
+const DP_A_76: f64 = 11.0/84.0;
+
+const DP_E_1: f64 = 71.0/57600.0;
+const DP_E_3: f64 = -71.0/16695.0;
+const DP_E_4: f64 = 71.0/1920.0;
+const DP_E_5: f64 = -17253.0/339200.0;
+const DP_E_6: f64 = 22.0/525.0;
+const DP_E_7: f64 = -1.0/40.0;
+
+const T_MAX: f64 = 1e5;
+const T_MIN: f64 = 1e-8;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub position: Vector3<f64>,
+    pub direction: Vector3<f64>,
+}
+
+impl Ray {
+    pub fn new(position: Vector3<f64>, direction: Vector3<f64>) -> Self {
+        Self {
+            position,
+            direction: direction.normalize(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RKState {
+    pub h: f64,
+    pub e_max: f64,
+    pub ray: Ray,
+    pub acceleration: Vector3<f64>,
+}
+
+impl RKState {
+    pub fn new(ray: Ray, h: f64, black_holes: &[BlackHole], metric: &dyn Metric) -> Self {
+        Self {
+            h,
+            e_max: 0.0,
+            ray,
+            acceleration: f(ray.position, ray.direction, black_holes, metric),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RayHit {
+    Horizon(Vector3<f64>),
+    Disk(Vector3<f64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub ray: Ray,
+    pub hits: Vec<RayHit>,
+    pub iterations: i32,
+    pub closest_distance: f64,
+    pub escaped: bool,
+    // disk plane crossings, the image order the ray ends up in
+    pub disk_crossings: i32,
+}
+
+impl Trace {
+    pub fn hit_horizon(&self) -> bool {
+        self.hits.iter().any(|hit| matches!(hit, RayHit::Horizon(_)))
+    }
+}
+
+fn position_of(black_hole: &BlackHole) -> Vector3<f64> {
+    black_hole.position.cast::<f64>().unwrap()
+}
+
+pub fn f(position: Vector3<f64>, direction: Vector3<f64>, black_holes: &[BlackHole], metric: &dyn Metric) -> Vector3<f64> {
+    let mut acceleration = Vector3::new(0.0, 0.0, 0.0);
+
+    for black_hole in black_holes {
+        acceleration += metric.acceleration(position - position_of(black_hole), direction, black_hole);
+    }
+
+    acceleration
+}
+
+fn shrink_step(h: f64, e_max: f64, exponent: f64) -> f64 {
+    let h_temp = 0.9 * h / e_max.powf(exponent);
+
+    if h >= 0.0 {
+        h_temp.max(0.1 * h)
+    } else {
+        h_temp.min(0.1 * h)
+    }
+}
+
+fn grow_step(h: f64, e_max: f64, details: &RayDetails) -> f64 {
+    if e_max > details.growth_threshold as f64 {
+        h * 0.9 * e_max.powf(-0.001)
+    } else {
+        h * details.growth_factor as f64
+    }
+}
+
+fn clamp_step(h: f64, details: &RayDetails) -> f64 {
+    h.clamp(details.min_step as f64, details.max_step as f64)
+}
+
+fn horizon_step(position: Vector3<f64>, black_holes: &[BlackHole], details: &RayDetails) -> f64 {
+    let mut limit = details.max_step as f64;
+
+    for black_hole in black_holes {
+        let horizon = black_hole.kerr_schild_radius(black_hole.horizon_radius()) as f64;
+        let gap = ((position - position_of(black_hole)).magnitude() - horizon).max(0.0);
+
+        limit = limit.min(0.5 * gap + details.min_step as f64);
+    }
+
+    limit
+}
+
+fn error_max(e: Vector3<f64>, yscal: Vector3<f64>) -> f64 {
+    (e.x/yscal.x).abs().max((e.y/yscal.y).abs()).max((e.z/yscal.z).abs())
+}
+
+pub fn next_ray_cash_karp(rk_state_in: RKState, black_holes: &[BlackHole], details: &RayDetails) -> RKState {
+    let metric = details.metric.metric();
+
+    let mut rk_state = rk_state_in;
+
+    rk_state.h = clamp_step(rk_state.h, details).min(horizon_step(rk_state.ray.position, black_holes, details));
+
+    let ray = rk_state.ray;
+
+    let mut k_1;
+    let mut k_2;
+    let mut k_3;
+    let mut k_4;
+    let mut k_5;
+    let mut k_6;
+
+    let dydx = f(ray.position, ray.direction, black_holes, metric);
+    let error_scale = details.error_scale as f64;
+    let yscal = Vector3::new(error_scale, error_scale, error_scale);
+    let eps = details.tolerance as f64;
+
+    loop {
+        let h = rk_state.h;
+
+        k_1 = dydx;
+        k_2 = f(ray.position + (A_21*k_1)*h, ray.direction, black_holes, metric);
+        k_3 = f(ray.position + (A_31*k_1 + A_32*k_2)*h, ray.direction, black_holes, metric);
+        k_4 = f(ray.position + (A_41*k_1 + A_42*k_2 + A_43*k_3)*h, ray.direction, black_holes, metric);
+        k_5 = f(ray.position + (A_51*k_1 + A_52*k_2 + A_53*k_3 + A_54*k_4)*h, ray.direction, black_holes, metric);
+        k_6 = f(ray.position + (A_61*k_1 + A_62*k_2 + A_63*k_3 + A_64*k_4 + A_65*k_5)*h, ray.direction, black_holes, metric);
+
+        let e = h * ((B_1-B_A_1)*k_1 + (B_2-B_A_2)*k_2 + (B_3-B_A_3)*k_3 + (B_4-B_A_4)*k_4 + (B_5-B_A_5)*k_5 + (B_6-B_A_6)*k_6);
+
+        rk_state.e_max = error_max(e, yscal) / eps;
+
+        if rk_state.e_max <= 1.0 || h <= details.min_step as f64 {
+            break;
+        }
+
+        rk_state.h = clamp_step(shrink_step(h, rk_state.e_max, 0.25), details);
+    }
+
+    rk_state.ray.direction += rk_state.h * (B_A_1*k_1 + B_A_2*k_2 + B_A_3*k_3 + B_A_4*k_4 + B_A_5*k_5 + B_A_6*k_6);
+    rk_state.ray.direction = rk_state.ray.direction.normalize();
+
+    rk_state.ray.position += ray.direction * rk_state.h;
+
+    rk_state.h = clamp_step(grow_step(rk_state.h, rk_state.e_max, details), details);
+
+    rk_state
+}
+
+pub fn next_ray_dormand_prince(rk_state_in: RKState, black_holes: &[BlackHole], details: &RayDetails) -> RKState {
+    let metric = details.metric.metric();
+
+    let mut rk_state = rk_state_in;
+
+    rk_state.h = clamp_step(rk_state.h, details).min(horizon_step(rk_state.ray.position, black_holes, details));
+
+    let x = rk_state.ray.position;
+    let v = rk_state.ray.direction;
+
+    let kx_1 = v;
+    let kv_1 = rk_state.acceleration;
+
+    let mut kx_7;
+    let mut kv_7;
+    let mut x_new;
+
+    let error_scale = details.error_scale as f64;
+    let yscal = Vector3::new(error_scale, error_scale, error_scale);
+    let eps = details.tolerance as f64;
+
+    loop {
+        let h = rk_state.h;
+
+        let kx_2 = v + h*(DP_A_21*kv_1);
+        let kv_2 = f(x + h*(DP_A_21*kx_1), kx_2, black_holes, metric);
+
+        let kx_3 = v + h*(DP_A_31*kv_1 + DP_A_32*kv_2);
+        let kv_3 = f(x + h*(DP_A_31*kx_1 + DP_A_32*kx_2), kx_3, black_holes, metric);
+
+        let kx_4 = v + h*(DP_A_41*kv_1 + DP_A_42*kv_2 + DP_A_43*kv_3);
+        let kv_4 = f(x + h*(DP_A_41*kx_1 + DP_A_42*kx_2 + DP_A_43*kx_3), kx_4, black_holes, metric);
+
+        let kx_5 = v + h*(DP_A_51*kv_1 + DP_A_52*kv_2 + DP_A_53*kv_3 + DP_A_54*kv_4);
+        let kv_5 = f(x + h*(DP_A_51*kx_1 + DP_A_52*kx_2 + DP_A_53*kx_3 + DP_A_54*kx_4), kx_5, black_holes, metric);
+
+        let kx_6 = v + h*(DP_A_61*kv_1 + DP_A_62*kv_2 + DP_A_63*kv_3 + DP_A_64*kv_4 + DP_A_65*kv_5);
+        let kv_6 = f(x + h*(DP_A_61*kx_1 + DP_A_62*kx_2 + DP_A_63*kx_3 + DP_A_64*kx_4 + DP_A_65*kx_5), kx_6, black_holes, metric);
+
+        kx_7 = v + h*(DP_A_71*kv_1 + DP_A_73*kv_3 + DP_A_74*kv_4 + DP_A_75*kv_5 + DP_A_76*kv_6);
+        x_new = x + h*(DP_A_71*kx_1 + DP_A_73*kx_3 + DP_A_74*kx_4 + DP_A_75*kx_5 + DP_A_76*kx_6);
+        kv_7 = f(x_new, kx_7, black_holes, metric);
+
+        let e_x = h * (DP_E_1*kx_1 + DP_E_3*kx_3 + DP_E_4*kx_4 + DP_E_5*kx_5 + DP_E_6*kx_6 + DP_E_7*kx_7);
+        let e_v = h * (DP_E_1*kv_1 + DP_E_3*kv_3 + DP_E_4*kv_4 + DP_E_5*kv_5 + DP_E_6*kv_6 + DP_E_7*kv_7);
+
+        rk_state.e_max = error_max(e_x, yscal).max(error_max(e_v, yscal)) / eps;
+
+        if rk_state.e_max <= 1.0 || h <= details.min_step as f64 {
+            break;
+        }
+
+        rk_state.h = clamp_step(shrink_step(h, rk_state.e_max, 0.2), details);
+    }
+
+    rk_state.ray.position = x_new;
+    rk_state.ray.direction = kx_7.normalize();
+    rk_state.acceleration = kv_7;
+
+    rk_state.h = clamp_step(grow_step(rk_state.h, rk_state.e_max, details), details);
+
+    rk_state
+}
+
+pub fn next_ray_rk4(in_ray: Ray, step_size: f64, black_holes: &[BlackHole], metric: &dyn Metric) -> Ray {
+    let mut ray = in_ray;
+
+    let h = step_size;
+    let x = ray.position;
+    let v = ray.direction;
+
+    let kx_1 = v;
+    let kv_1 = f(x, kx_1, black_holes, metric);
+
+    let kx_2 = v + 0.5*h*kv_1;
+    let kv_2 = f(x + 0.5*h*kx_1, kx_2, black_holes, metric);
+
+    let kx_3 = v + 0.5*h*kv_2;
+    let kv_3 = f(x + 0.5*h*kx_2, kx_3, black_holes, metric);
+
+    let kx_4 = v + h*kv_3;
+    let kv_4 = f(x + h*kx_3, kx_4, black_holes, metric);
+
+    ray.position += h/6.0 * (kx_1 + 2.0*kx_2 + 2.0*kx_3 + kx_4);
+    ray.direction = (v + h/6.0 * (kv_1 + 2.0*kv_2 + 2.0*kv_3 + kv_4)).normalize();
+
+    ray
+}
+
+// Kick-drift-kick, symplectic for a single non-spinning hole, see next_ray_verlet in ray.wgsl.
+pub fn next_ray_verlet(in_ray: Ray, step_size: f64, black_holes: &[BlackHole], metric: &dyn Metric) -> Ray {
+    let mut ray = in_ray;
+
+    let half_direction = ray.direction + 0.5 * step_size * f(ray.position, ray.direction, black_holes, metric);
+
+    ray.position += half_direction * step_size;
+    ray.direction = (half_direction + 0.5 * step_size * f(ray.position, half_direction, black_holes, metric)).normalize();
+
+    ray
+}
+
+pub fn next_ray_euler(in_ray: Ray, step_size: f64, black_holes: &[BlackHole], metric: &dyn Metric) -> Ray {
+    let mut ray = in_ray;
+
+    ray.direction += f(ray.position, ray.direction, black_holes, metric) * step_size;
+    ray.direction = ray.direction.normalize();
+
+    ray.position += ray.direction * step_size;
+
+    ray
+}
+
+pub fn next_ray(rk_state_in: RKState, black_holes: &[BlackHole], details: &RayDetails) -> RKState {
+    let metric = details.metric.metric();
+
+    let mut rk_state = rk_state_in;
+
+    match details.integration_method {
+        IntegrationMethod::Euler => rk_state.ray = next_ray_euler(rk_state.ray, rk_state.h, black_holes, metric),
+        IntegrationMethod::CashKarp => rk_state = next_ray_cash_karp(rk_state, black_holes, details),
+        IntegrationMethod::RK4 => rk_state.ray = next_ray_rk4(rk_state.ray, rk_state.h, black_holes, metric),
+        IntegrationMethod::DormandPrince => rk_state = next_ray_dormand_prince(rk_state, black_holes, details),
+        IntegrationMethod::Verlet => rk_state.ray = next_ray_verlet(rk_state.ray, rk_state.h, black_holes, metric),
+    }
+
+    rk_state
+}
+
+pub fn hit_sphere(ray: &Ray, radius: f64, center: Vector3<f64>, t_min: f64, t_max: f64) -> Option<f64> {
+    let oc = ray.position - center;
+    let a = ray.direction.dot(ray.direction);
+    let b = 2.0 * oc.dot(ray.direction);
+    let c = oc.dot(oc) - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
+    let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+    let mut t_closest = t_max;
+
+    if t1 > t_min && t1 < t_max {
+        t_closest = t1;
+    }
+
+    if t2 > t_min && t2 < t_max && t2 < t_closest {
+        t_closest = t2;
+    }
+
+    if t_closest < t_max && t_closest > t_min {
+        Some(t_closest)
+    } else {
+        None
+    }
+}
+
+pub fn hit_disk(ray: &Ray, black_hole: &BlackHole, t_min: f64, t_max: f64) -> Option<f64> {
+    let normal = black_hole.disk_normal().cast::<f64>().unwrap();
+    let position = position_of(black_hole);
+
+    let denom = normal.dot(ray.direction);
+    let t = (position - ray.position).dot(normal) / denom;
+
+    if t < t_max && t > t_min {
+        let intersection = ray.position + ray.direction * t;
+        let distance_from_center = (intersection - position).magnitude();
+
+        if distance_from_center >= black_hole.disk_inner_radius() as f64
+            && distance_from_center <= black_hole.accretion_disk_outer as f64 {
+            return Some(t);
+        }
+    }
+
+    None
+}
+
+// With spin the horizon is squashed along the axis, r+ at the poles and
+// sqrt(r+^2 + a^2) around the equator. Scaling it back into a unit sphere
+// keeps t, since the direction isn't normalised again
+pub fn hit_horizon(ray: &Ray, black_hole: &BlackHole, t_min: f64, t_max: f64) -> Option<f64> {
+    let axis = black_hole.disk_normal().cast::<f64>().unwrap();
+    let polar = black_hole.horizon_radius() as f64;
+    let equatorial = black_hole.kerr_schild_radius(black_hole.horizon_radius()) as f64;
+
+    let squash = |v: Vector3<f64>| {
+        let along = v.dot(axis) * axis;
+
+        along / polar + (v - along) / equatorial
+    };
+
+    let scaled = Ray {
+        position: squash(ray.position - position_of(black_hole)),
+        direction: squash(ray.direction),
+    };
+
+    hit_sphere(&scaled, 1.0, Vector3::new(0.0, 0.0, 0.0), t_min, t_max)
+}
+
+// Mirrors hit_black_hole without the shading, so the closest of horizon and disk wins.
+fn hit_black_hole(ray: &Ray, black_hole: &BlackHole, t_min: f64, t_max: f64) -> Option<(f64, RayHit)> {
+    let horizon = hit_horizon(ray, black_hole, t_min, t_max);
+    let disk = hit_disk(ray, black_hole, t_min, t_max);
+
+    match (horizon, disk) {
+        (Some(t_horizon), Some(t_disk)) if t_disk < t_horizon =>
+            Some((t_disk, RayHit::Disk(ray.position + ray.direction * t_disk))),
+        (Some(t_horizon), _) =>
+            Some((t_horizon, RayHit::Horizon(ray.position + ray.direction * t_horizon))),
+        (None, Some(t_disk)) =>
+            Some((t_disk, RayHit::Disk(ray.position + ray.direction * t_disk))),
+        (None, None) => None,
+    }
+}
+
+fn hit_black_holes(ray: &Ray, black_holes: &[BlackHole], t_min: f64, t_max: f64) -> Option<(f64, RayHit)> {
+    black_holes.iter()
+        .filter_map(|black_hole| hit_black_hole(ray, black_hole, t_min, t_max))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+fn inside_relativity(position: Vector3<f64>, black_holes: &[BlackHole]) -> bool {
+    black_holes.iter().any(|black_hole| {
+        (position - position_of(black_hole)).magnitude() < black_hole.relativity_sphere_radius as f64
+    })
+}
+
+fn feather_amount(position: Vector3<f64>, black_holes: &[BlackHole]) -> f64 {
+    let mut linear_mix_amount: f64 = 1.0;
+
+    for black_hole in black_holes {
+        let bh_radius = black_hole.relativity_sphere_radius as f64;
+        let feather_width = bh_radius * black_hole.feather_amount as f64;
+        let feather_start = bh_radius - feather_width;
+        let dist = (position - position_of(black_hole)).magnitude();
+
+        linear_mix_amount = linear_mix_amount.min(((dist - feather_start) / feather_width).clamp(0.0, 1.0));
+    }
+
+    linear_mix_amount
+}
+
+// Unlike the shader this does not shade anything: disk crossings are recorded
+// and the ray carries on, the horizon stops it. Meshes are not traced.
+pub fn trace_ray(ray: Ray, black_holes: &[BlackHole], details: &RayDetails) -> Trace {
+    let metric = details.metric.metric();
+
+    // same as the gpu, spin and charge the metric ignores don't move the horizon
+    let black_holes: Vec<BlackHole> = black_holes.iter().map(|black_hole| black_hole.for_metric(details.metric)).collect();
+    let black_holes = black_holes.as_slice();
+
+    let mut relativity = inside_relativity(ray.position, black_holes);
+
+    let mut curr_ray = ray;
+    let mut prev_ray;
+
+    let mut step_size = details.step_size as f64;
+
+    let mut rk_state = RKState::new(curr_ray, step_size, black_holes, metric);
+
+    let mut hits = Vec::new();
+    let mut escaped = false;
+    let mut disk_crossings = 0;
+
+    let mut i = 0;
+    let mut closest_distance = black_holes.iter()
+        .map(|black_hole| (ray.position - position_of(black_hole)).magnitude())
+        .fold(f64::MAX, f64::min);
+
+    let mut entry_direction = ray.direction;
+    let mut linear_mix_amount = feather_amount(ray.position, black_holes);
+
+    while i < details.max_iterations {
+        let mut closest_hit = None;
+
+        if relativity {
+            prev_ray = curr_ray;
+
+            rk_state = next_ray(rk_state, black_holes, details);
+            curr_ray = rk_state.ray;
+
+            // the adaptive methods leave h grown for the next step, the hit
+            // test wants the step that was just taken
+            step_size = (curr_ray.position - prev_ray.position).magnitude();
+
+            for black_hole in black_holes {
+                closest_distance = closest_distance.min((curr_ray.position - position_of(black_hole)).magnitude());
+            }
+
+            linear_mix_amount = linear_mix_amount.min(feather_amount(curr_ray.position, black_holes));
+
+            prev_ray.direction = curr_ray.direction;
+
+            closest_hit = hit_black_holes(&prev_ray, black_holes, T_MIN, step_size);
+
+            for black_hole in black_holes {
+                let normal = black_hole.disk_normal().cast::<f64>().unwrap();
+                let prev_height = (prev_ray.position - position_of(black_hole)).dot(normal);
+                let curr_height = (curr_ray.position - position_of(black_hole)).dot(normal);
+
+                if prev_height * curr_height < 0.0 {
+                    disk_crossings += 1;
+                }
+            }
+
+            if !inside_relativity(curr_ray.position, black_holes) {
+                relativity = false;
+
+                let mix_amount = linear_mix_amount.powi(2);
+
+                curr_ray.direction += (entry_direction - curr_ray.direction) * mix_amount;
+            }
+        } else {
+            let sphere_hit = black_holes.iter()
+                .filter_map(|black_hole| hit_sphere(
+                    &curr_ray,
+                    black_hole.relativity_sphere_radius as f64,
+                    position_of(black_hole),
+                    T_MIN,
+                    T_MAX,
+                ))
+                .min_by(f64::total_cmp);
+
+            match sphere_hit {
+                Some(t) => {
+                    curr_ray.position += curr_ray.direction * t;
+                    relativity = true;
+
+                    entry_direction = curr_ray.direction;
+                    linear_mix_amount = 1.0;
+                    rk_state = RKState::new(curr_ray, rk_state.h, black_holes, metric);
+                },
+                None => {
+                    escaped = true;
+                    break;
+                }
+            }
+        }
+
+        if let Some((_, hit)) = closest_hit {
+            hits.push(hit);
+
+            if let RayHit::Horizon(_) = hit {
+                break;
+            }
+        }
+
+        i += 1;
+    }
+
+    Trace {
+        ray: curr_ray,
+        hits,
+        iterations: i,
+        closest_distance,
+        escaped,
+        disk_crossings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector3};
+
+    use crate::{renderer::pipelines::ray_pipeline::{IntegrationMethod, RayDetails}, scene::{blackhole::BlackHole, metric::MetricKind}};
+
+    use super::{next_ray_cash_karp, next_ray_dormand_prince, trace_ray, RKState, Ray};
+
+    fn details(integration_method: IntegrationMethod, step_size: f32) -> RayDetails {
+        RayDetails {
+            integration_method,
+            step_size,
+            max_iterations: 100000,
+            tolerance: 1e-6,
+            metric: MetricKind::Schwarzschild,
+            ..Default::default()
+        }
+    }
+
+    fn black_hole(relativity_sphere_radius: f32) -> BlackHole {
+        BlackHole {
+            relativity_sphere_radius,
+            ..BlackHole::new()
+        }
+    }
+
+    // a ray passing the hole at impact parameter b, from z = -length to +length
+    fn deflection(b: f64, length: f64, details: &RayDetails) -> f64 {
+        let black_hole = black_hole((length * 1.5) as f32);
+        let ray = Ray::new(Vector3::new(b, 0.0, -length), Vector3::unit_z());
+
+        let trace = trace_ray(ray, &[black_hole], details);
+
+        assert!(trace.escaped);
+
+        trace.ray.direction.angle(Vector3::unit_z()).0
+    }
+
+    #[test]
+    fn radial_ray_is_captured() {
+        let ray = Ray::new(Vector3::new(0.0, 0.0, -15.0), Vector3::unit_z());
+
+        for method in [IntegrationMethod::Euler, IntegrationMethod::RK4, IntegrationMethod::Verlet, IntegrationMethod::CashKarp, IntegrationMethod::DormandPrince] {
+            let trace = trace_ray(ray, &[black_hole(20.0)], &details(method, 0.1));
+
+            assert!(trace.hit_horizon(), "{:?} missed the horizon", method);
+            assert!(!trace.escaped);
+        }
+    }
+
+    #[test]
+    fn distant_ray_deflects_by_4m_over_b() {
+        let mass = BlackHole::new().mass as f64;
+        let b = 200.0 * mass;
+
+        // the ray only sees the part of the bend between its ends
+        let length = 20.0 * b;
+        let expected = 4.0 * mass / b * length / (length * length + b * b).sqrt();
+
+        let angle = deflection(b, length, &details(IntegrationMethod::RK4, 1.0));
+
+        assert!((angle - expected).abs() < 0.05 * expected, "{} vs {}", angle, expected);
+    }
+
+    #[test]
+    fn adaptive_steps_stop_short_of_the_horizon() {
+        let black_hole = BlackHole::new();
+        let details = RayDetails { max_step: 50.0, ..details(IntegrationMethod::CashKarp, 50.0) };
+
+        let ray = Ray::new(Vector3::new(0.0, 0.0, -3.0), Vector3::unit_z());
+        let rk_state = RKState::new(ray, 50.0, &[black_hole], details.metric.metric());
+
+        for next in [next_ray_cash_karp, next_ray_dormand_prince] {
+            let moved = next(rk_state, &[black_hole], &details);
+
+            // still on the near side rather than through the hole
+            assert!(moved.ray.position.z < -black_hole.horizon_radius() as f64, "{:?}", moved.ray.position);
+        }
+    }
+
+    #[test]
+    fn integrators_agree() {
+        let reference = deflection(5.0, 40.0, &details(IntegrationMethod::RK4, 0.01));
+
+        assert!(reference > 0.1);
+
+        for method in [IntegrationMethod::Euler, IntegrationMethod::Verlet, IntegrationMethod::CashKarp, IntegrationMethod::DormandPrince] {
+            let angle = deflection(5.0, 40.0, &details(method, 0.01));
+
+            assert!((angle - reference).abs() < 0.01 * reference, "{:?} {} vs rk4 {}", method, angle, reference);
+        }
+    }
+    #[test]
+    fn spinning_metrics_reduce_to_schwarzschild() {
+        let reference = deflection(5.0, 40.0, &details(IntegrationMethod::RK4, 0.01));
+
+        for metric in [MetricKind::Kerr, MetricKind::KerrNewman] {
+            let angle = deflection(5.0, 40.0, &RayDetails { metric, ..details(IntegrationMethod::RK4, 0.01) });
+
+            assert!((angle - reference).abs() < 0.01 * reference, "{:?} {} vs {}", metric, angle, reference);
+        }
+    }
+
+    // equatorial rays against the kerr critical impact parameters,
+    // b = -+a + 6M cos(acos(-+a/M) / 3) for light going with and against the spin
+    #[test]
+    fn spin_makes_the_shadow_lopsided() {
+        let black_hole = BlackHole { spin: 0.9, ..black_hole(60.0) };
+        let m = black_hole.mass as f64;
+        let a = black_hole.spin as f64 * m;
+
+        let prograde = -a + 6.0 * m * ((-a / m).acos() / 3.0).cos();
+        let retrograde = a + 6.0 * m * ((a / m).acos() / 3.0).cos();
+
+        // photons arrive along -direction, so this side orbits with the spin
+        let axis = black_hole.angular_momentum().cast::<f64>().unwrap().normalize();
+        let side = axis.cross(Vector3::unit_x()).normalize();
+        let direction = side.cross(axis);
+
+        let captured = |b: f64| {
+            let ray = Ray::new(-50.0 * direction + b * side, direction);
+            let details = RayDetails { metric: MetricKind::Kerr, ..details(IntegrationMethod::DormandPrince, 0.01) };
+
+            trace_ray(ray, &[black_hole], &details).hit_horizon()
+        };
+
+        assert!(captured(0.95 * prograde));
+        assert!(!captured(1.05 * prograde));
+        assert!(captured(-0.95 * retrograde));
+        assert!(!captured(-1.05 * retrograde));
+    }
+}
@@ -0,0 +1,260 @@
+use cgmath::{InnerSpace, Vector3};
+
+use super::blackhole::BlackHole;
+
+// a spacetime around each hole. The acceleration is the photon force used by
+// the integrators with r the position relative to the hole, the lapse is
+// sqrt(-g_tt) which sets the redshift of a static observer, and wgsl is the
+// same pair for the gpu, defining
+//     fn metric_acceleration(r: vec3<f32>, direction: vec3<f32>, black_hole: BlackHole) -> vec3<f32>
+//     fn metric_lapse(r: vec3<f32>, black_hole: BlackHole) -> f32
+//     const METRIC_USES_LUT: bool
+// which the ray pipeline appends to ray.wgsl
+pub trait Metric {
+    fn acceleration(&self, r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64>;
+
+    fn lapse(&self, r: Vector3<f64>, black_hole: &BlackHole) -> f64;
+
+    fn wgsl(&self) -> &'static str;
+}
+
+pub struct Schwarzschild;
+pub struct Kerr;
+pub struct ReissnerNordstrom;
+pub struct KerrNewman;
+
+fn mass_term(r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+    let dist = r.magnitude();
+    let h2 = r.cross(direction).magnitude2();
+
+    -3.0 * black_hole.mass as f64 * h2 * r / dist.powi(5)
+}
+
+fn charge_term(r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+    let dist = r.magnitude();
+    let h2 = r.cross(direction).magnitude2();
+    let q = black_hole.charge_radius() as f64;
+
+    2.0 * q * q * h2 * r / dist.powi(6)
+}
+
+fn static_lapse(r: Vector3<f64>, black_hole: &BlackHole, q: f64) -> f64 {
+    let dist = r.magnitude();
+
+    (1.0 - 2.0 * black_hole.mass as f64 / dist + q * q / (dist * dist)).max(0.0).sqrt()
+}
+
+// kerr-schild radius of p, the horizon is the oblate spheroid r = r+
+fn kerr_schild_radius(p: Vector3<f64>, spin: Vector3<f64>) -> f64 {
+    let z = p.dot(spin);
+    let w = p.magnitude2() - spin.magnitude2();
+
+    (0.5 * w + (0.25 * w * w + z * z).sqrt()).sqrt()
+}
+
+// kerr-newman in kerr-schild form, g = η + f l l with the spin vector J/M.
+// Light follows the christoffel symbols of that metric, which depend on the
+// direction of travel, so rays going with the spin are dragged along and the
+// shadow is lopsided. Without spin it reduces to the force laws above
+fn kerr_schild_acceleration(p: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole, q: f64) -> Vector3<f64> {
+    let m = black_hole.mass as f64;
+    let spin = black_hole.angular_momentum().cast::<f64>().unwrap() / m;
+    let a2 = spin.magnitude2();
+    let q2 = q * q;
+
+    let z = p.dot(spin);
+    let r = kerr_schild_radius(p, spin);
+    let r2 = r * r;
+    let k = r2 + a2;
+    let den = r2 * r2 + z * z;
+
+    let n = r2 * (2.0 * m * r - q2);
+    let f = n / den;
+    let l = (r * p + p.cross(spin) + z * spin / r) / k;
+
+    let grad_r = r * (r2 * p + z * spin) / den;
+    let df_dr = (2.0 * r * (3.0 * m * r - q2) * den - n * 4.0 * r * r2) / (den * den);
+    let grad_f = df_dr * grad_r - 2.0 * n * z / (den * den) * spin;
+    let dl_dr = (p - z * spin / r2 - 2.0 * r * l) / k;
+
+    // photons arrive along -direction, the traced ray runs them backwards
+    let v = -direction;
+    let lv = l.dot(v);
+
+    // u^t from g(u, u) = 0, the root that stays future pointing in the ergosphere
+    let a = f - 1.0;
+    let b = 2.0 * f * lv;
+    let c = v.magnitude2() + f * lv * lv;
+    let u_t = 2.0 * c / (-b + (b * b - 4.0 * a * c).max(0.0).sqrt());
+
+    let s = u_t + lv;
+
+    // derivatives of l.u and f along v and across space with u held fixed
+    let dl_v = dl_dr * grad_r.dot(v) + (r * v + v.cross(spin) + v.dot(spin) * spin / r) / k;
+    let ds_v = dl_v.dot(v);
+    let df_v = grad_f.dot(v);
+    let grad_s = dl_dr.dot(v) * grad_r + (r * v + spin.cross(v) + v.dot(spin) * spin / r) / k;
+
+    // Γ_μαβ u^α u^β, then raised with g^μν = η^μν - f l^μ l^ν
+    let lower_t = df_v * s + f * ds_v;
+    let lower = lower_t * l + f * s * dl_v - 0.5 * (grad_f * s * s + 2.0 * f * s * grad_s);
+
+    -(lower - f * (l.dot(lower) - lower_t) * l)
+}
+
+// sqrt(1 - f), which reaches zero at the ergosphere rather than the horizon
+fn kerr_schild_lapse(p: Vector3<f64>, black_hole: &BlackHole, q: f64) -> f64 {
+    let m = black_hole.mass as f64;
+    let spin = black_hole.angular_momentum().cast::<f64>().unwrap() / m;
+
+    let z = p.dot(spin);
+    let r = kerr_schild_radius(p, spin);
+    let f = r * r * (2.0 * m * r - q * q) / (r * r * r * r + z * z);
+
+    (1.0 - f).max(0.0).sqrt()
+}
+
+impl Metric for Schwarzschild {
+    fn acceleration(&self, r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+        mass_term(r, direction, black_hole)
+    }
+
+    fn lapse(&self, r: Vector3<f64>, black_hole: &BlackHole) -> f64 {
+        static_lapse(r, black_hole, 0.0)
+    }
+
+    fn wgsl(&self) -> &'static str {
+        include_str!("../renderer/shaders/metrics/schwarzschild.wgsl")
+    }
+}
+
+impl Metric for Kerr {
+    fn acceleration(&self, r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+        kerr_schild_acceleration(r, direction, black_hole, 0.0)
+    }
+
+    fn lapse(&self, r: Vector3<f64>, black_hole: &BlackHole) -> f64 {
+        kerr_schild_lapse(r, black_hole, 0.0)
+    }
+
+    fn wgsl(&self) -> &'static str {
+        concat!(
+            include_str!("../renderer/shaders/metrics/kerr_schild.wgsl"),
+            include_str!("../renderer/shaders/metrics/kerr.wgsl"),
+        )
+    }
+}
+
+impl Metric for ReissnerNordstrom {
+    fn acceleration(&self, r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+        mass_term(r, direction, black_hole) + charge_term(r, direction, black_hole)
+    }
+
+    fn lapse(&self, r: Vector3<f64>, black_hole: &BlackHole) -> f64 {
+        static_lapse(r, black_hole, black_hole.charge_radius() as f64)
+    }
+
+    fn wgsl(&self) -> &'static str {
+        include_str!("../renderer/shaders/metrics/reissner_nordstrom.wgsl")
+    }
+}
+
+impl Metric for KerrNewman {
+    fn acceleration(&self, r: Vector3<f64>, direction: Vector3<f64>, black_hole: &BlackHole) -> Vector3<f64> {
+        kerr_schild_acceleration(r, direction, black_hole, black_hole.charge_radius() as f64)
+    }
+
+    fn lapse(&self, r: Vector3<f64>, black_hole: &BlackHole) -> f64 {
+        kerr_schild_lapse(r, black_hole, black_hole.charge_radius() as f64)
+    }
+
+    fn wgsl(&self) -> &'static str {
+        concat!(
+            include_str!("../renderer/shaders/metrics/kerr_schild.wgsl"),
+            include_str!("../renderer/shaders/metrics/kerr_newman.wgsl"),
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MetricKind {
+    #[default]
+    Schwarzschild,
+    Kerr,
+    ReissnerNordstrom,
+    KerrNewman,
+}
+
+impl MetricKind {
+    pub fn uses_spin(&self) -> bool {
+        matches!(self, MetricKind::Kerr | MetricKind::KerrNewman)
+    }
+
+    pub fn uses_charge(&self) -> bool {
+        matches!(self, MetricKind::ReissnerNordstrom | MetricKind::KerrNewman)
+    }
+
+    pub fn metric(&self) -> &'static dyn Metric {
+        match self {
+            MetricKind::Schwarzschild => &Schwarzschild,
+            MetricKind::Kerr => &Kerr,
+            MetricKind::ReissnerNordstrom => &ReissnerNordstrom,
+            MetricKind::KerrNewman => &KerrNewman,
+        }
+    }
+}
+
+impl From<MetricKind> for String {
+    fn from(value: MetricKind) -> Self {
+        match value {
+            MetricKind::Schwarzschild => "Schwarzschild".into(),
+            MetricKind::Kerr => "Kerr".into(),
+            MetricKind::ReissnerNordstrom => "Reissner-Nordström".into(),
+            MetricKind::KerrNewman => "Kerr-Newman".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector3};
+
+    use crate::scene::blackhole::BlackHole;
+
+    use super::MetricKind;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} vs {}", a, b);
+    }
+
+    // the closed forms the wgsl snippets evaluate, sqrt(1 - 2M/r + Q^2/r^2)
+    // for the static holes and sqrt(1 - 2Mr/(r^2 + a^2)) on the spin axis
+    // and sqrt(1 - 2M/r) in the equator, with r the boyer-lindquist radius,
+    // for the spinning ones
+    #[test]
+    fn lapse_matches_the_snippets() {
+        let black_hole = BlackHole { mass: 1.0, spin: 0.6, charge: 0.5, ..BlackHole::new() };
+        let axis = black_hole.angular_momentum().cast::<f64>().unwrap().normalize();
+        let equator = axis.cross(Vector3::unit_x()).normalize();
+        let (a, q) = (0.6, 0.5);
+
+        let schwarzschild = MetricKind::Schwarzschild.metric();
+        assert_close(schwarzschild.lapse(4.0 * equator, &black_hole), 0.5_f64.sqrt());
+        assert_close(schwarzschild.lapse(1.5 * axis, &black_hole), 0.0);
+
+        let reissner_nordstrom = MetricKind::ReissnerNordstrom.metric();
+        assert_close(reissner_nordstrom.lapse(2.0 * equator, &black_hole), q / 2.0);
+
+        for (metric, q) in [(MetricKind::Kerr, 0.0), (MetricKind::KerrNewman, q)] {
+            let metric = metric.metric();
+
+            for r in [3.0_f64, 10.0] {
+                let on_axis = 1.0 - (2.0 * r - q * q) / (r * r + a * a);
+                let in_equator = 1.0 - 2.0 / r + q * q / (r * r);
+
+                assert_close(metric.lapse(r * axis, &black_hole), on_axis.sqrt());
+                assert_close(metric.lapse((r * r + a * a).sqrt() * equator, &black_hole), in_equator.sqrt());
+            }
+        }
+    }
+}
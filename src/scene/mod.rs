@@ -1,19 +1,29 @@
+use cgmath::InnerSpace;
 use winit::keyboard::KeyCode;
 
 use crate::{input_manager::InputManager, renderer::{material::MaterialArrayBuffer, model, triangle::ModelArrayBuffer}, timer::Timer};
 
-use self::{blackhole::BlackHole, camera::Camera};
+use self::{animation::Timeline, blackhole::{BlackHole, BlackHoleArrayBuffer}, camera::{Camera, ObserverMotion}, wormhole::WormholeArrayBuffer};
 
 pub mod camera;
 pub mod blackhole;
+pub mod geodesic;
+pub mod deflection;
+pub mod timelike;
+pub mod wormhole;
+pub mod metric;
+pub mod scene_file;
+pub mod animation;
 
 pub struct Scene {
-    pub black_hole: BlackHole,
+    pub black_holes: BlackHoleArrayBuffer,
+    pub wormholes: WormholeArrayBuffer,
     pub camera: Camera,
     pub camera_move_speed: f32,
     pub camera_rotate_speed: f32,
     pub materials: MaterialArrayBuffer,
     pub models: ModelArrayBuffer,
+    pub timeline: Timeline,
 }
 
 impl Scene {
@@ -25,13 +35,18 @@ impl Scene {
             &mut models,
         );
 
+        let mut black_holes = BlackHoleArrayBuffer::new();
+        black_holes.insert(BlackHole::new());
+
         Self {
-            black_hole: BlackHole::new(),
+            black_holes,
+            wormholes: WormholeArrayBuffer::new(),
             camera: Camera::new(),
             camera_move_speed: 7.5,
             camera_rotate_speed: 0.15,
             materials: MaterialArrayBuffer::new(),
             models,
+            timeline: Timeline::new(),
         }
     }
 
@@ -39,24 +54,6 @@ impl Scene {
         let dt = timer.delta_time().as_secs_f32();
         let camera = &mut self.camera;
 
-        if input_manager.is_key_down(KeyCode::KeyW) {
-            camera.move_camera(self.camera_move_speed * dt * camera.forward());
-        } else if input_manager.is_key_down(KeyCode::KeyS) {
-            camera.move_camera(self.camera_move_speed * dt * -camera.forward());
-        }
-
-        if input_manager.is_key_down(KeyCode::KeyD) {
-            camera.move_camera(self.camera_move_speed * dt * camera.right());
-        } else if input_manager.is_key_down(KeyCode::KeyA) {
-            camera.move_camera(self.camera_move_speed * dt * -camera.right());
-        }
-
-        if input_manager.is_key_down(KeyCode::KeyQ) {
-            camera.move_camera(self.camera_move_speed * dt * camera.up());
-        } else if input_manager.is_key_down(KeyCode::KeyE) {
-            camera.move_camera(self.camera_move_speed * dt * -camera.up());
-        }
-
         if input_manager.is_left_mouse_down() {
             let (x, y) = input_manager.mouse_move();
             let yaw = x as f32 * self.camera_rotate_speed * dt;
@@ -69,16 +66,78 @@ impl Scene {
         let pitch = y * self.camera_rotate_speed * dt * 10.0;
         camera.rotate_camera(yaw, pitch);
 
-        let (x, y) = input_manager.joy_left();
-        camera.move_camera(self.camera_move_speed * dt * camera.forward() * 1.5 * y);
-        camera.move_camera(self.camera_move_speed * dt * camera.right() * 1.5 * x);
+        // the geodesic controller moves the camera itself in update_motion
+        if camera.motion != ObserverMotion::Geodesic {
+            if input_manager.is_key_down(KeyCode::KeyW) {
+                camera.move_camera(self.camera_move_speed * dt * camera.forward());
+            } else if input_manager.is_key_down(KeyCode::KeyS) {
+                camera.move_camera(self.camera_move_speed * dt * -camera.forward());
+            }
+
+            if input_manager.is_key_down(KeyCode::KeyD) {
+                camera.move_camera(self.camera_move_speed * dt * camera.right());
+            } else if input_manager.is_key_down(KeyCode::KeyA) {
+                camera.move_camera(self.camera_move_speed * dt * -camera.right());
+            }
+
+            if input_manager.is_key_down(KeyCode::KeyQ) {
+                camera.move_camera(self.camera_move_speed * dt * camera.up());
+            } else if input_manager.is_key_down(KeyCode::KeyE) {
+                camera.move_camera(self.camera_move_speed * dt * -camera.up());
+            }
+
+            let (x, y) = input_manager.joy_left();
+            camera.move_camera(self.camera_move_speed * dt * camera.forward() * 1.5 * y);
+            camera.move_camera(self.camera_move_speed * dt * camera.right() * 1.5 * x);
+
+            let (_, y) = input_manager.dpad();
+            camera.move_camera(self.camera_move_speed * dt * camera.up() * y);
+        }
 
-        let (_, y) = input_manager.dpad();
-        camera.move_camera(self.camera_move_speed * dt * camera.up() * y);
+        self.advance(dt);
 
         // let time = timer.total_time().as_secs_f32() * 0.25;
         // self.models.get_mut(0).unwrap().position.x = time.sin() * 70.0;
     }
+
+    // moves everything that isn't driven by input on by dt
+    pub fn advance(&mut self, dt: f32) {
+        let camera = &mut self.camera;
+
+        let nearest = self.black_holes.iter().min_by(|a, b| {
+            let a_dist = (a.position - camera.position).magnitude2();
+            let b_dist = (b.position - camera.position).magnitude2();
+            a_dist.total_cmp(&b_dist)
+        });
+
+        if let Some(black_hole) = nearest {
+            camera.update_motion(black_hole, dt);
+        }
+
+        // keyframes win over the controls while playing or scrubbing
+        self.timeline.update(dt, &mut self.camera, &mut self.black_holes);
+
+        for black_hole in self.black_holes.iter_mut() {
+            black_hole.disk_phase = (black_hole.disk_phase + black_hole.rotation_speed * dt) % std::f32::consts::TAU;
+        }
+    }
+
+    // back to the start of the timeline, where recordings begin
+    pub fn rewind(&mut self) {
+        self.timeline.set_time(0.0);
+        self.timeline.playing = !self.timeline.is_empty();
+        self.timeline.apply(&mut self.camera, &mut self.black_holes);
+
+        for black_hole in self.black_holes.iter_mut() {
+            black_hole.disk_phase = 0.0;
+        }
+    }
+
+    // keyframed poses follow the hole that takes the removed one's index
+    pub fn remove_black_hole(&mut self, index: usize) {
+        self.timeline.remove_black_hole(index, self.black_holes.size());
+        self.black_holes.remove(index);
+    }
 }
 
 impl Default for Scene {
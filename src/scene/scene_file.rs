@@ -0,0 +1,256 @@
+use std::{fmt, fs, io, path::Path};
+
+use cgmath::Vector3;
+
+use crate::renderer::{core::RenderCore, material::{Material, MAX_MATERIALS}, pipelines::{fxaa_pipline::FXAADetails, mix_pipeline::MixDetails, ray_pipeline::RayDetails}};
+
+use super::{animation::Timeline, blackhole::{BlackHole, MAX_BLACK_HOLES}, camera::Camera, wormhole::{Wormhole, MAX_WORMHOLES}, Scene};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(error) => write!(f, "{}", error),
+            SceneFileError::Parse(error) => write!(f, "{}", error),
+            SceneFileError::Write(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+// where a loaded mesh sits, the geometry itself still comes from its obj
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ModelPlacement {
+    pub ident: String,
+    pub position: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub visible: i32,
+}
+
+// everything needed to reproduce a render, saved as ron. Missing fields take
+// the startup scene's values so older files still load
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub camera: Camera,
+    pub camera_move_speed: f32,
+    pub camera_rotate_speed: f32,
+    pub black_holes: Vec<BlackHole>,
+    pub wormholes: Vec<Wormhole>,
+    pub materials: Vec<Material>,
+    pub models: Vec<ModelPlacement>,
+    pub ray_details: RayDetails,
+    pub fxaa_details: FXAADetails,
+    pub mix_details: MixDetails,
+    pub timeline: Timeline,
+}
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        Self {
+            camera: Camera::new(),
+            camera_move_speed: 7.5,
+            camera_rotate_speed: 0.15,
+            black_holes: vec![BlackHole::new()],
+            wormholes: Vec::new(),
+            materials: Vec::new(),
+            models: Vec::new(),
+            ray_details: RayDetails::default(),
+            fxaa_details: FXAADetails::default(),
+            mix_details: MixDetails { mix_ratio: 0.7 },
+            timeline: Timeline::new(),
+        }
+    }
+}
+
+impl SceneFile {
+    pub fn capture(scene: &Scene, core: &RenderCore) -> Self {
+        Self {
+            camera: scene.camera.clone(),
+            camera_move_speed: scene.camera_move_speed,
+            camera_rotate_speed: scene.camera_rotate_speed,
+            black_holes: scene.black_holes.iter().copied().collect(),
+            wormholes: scene.wormholes.iter().copied().collect(),
+            materials: scene.materials.iter().copied().collect(),
+            models: scene.models.iter()
+                .map(|model| ModelPlacement {
+                    ident: model.ident(),
+                    position: model.position,
+                    rotation: model.rotation,
+                    visible: model.visible,
+                })
+                .collect(),
+            ray_details: core.ray_details,
+            fxaa_details: core.fxaa_details,
+            mix_details: core.mix_details,
+            timeline: scene.timeline.clone(),
+        }
+    }
+
+    // placements are matched to the scene's models by ident, models the file
+    // does not mention are left alone. Anything that can't be reproduced is
+    // logged rather than dropped quietly
+    pub fn apply(self, scene: &mut Scene, core: &mut RenderCore) {
+        scene.camera = self.camera;
+        scene.camera_move_speed = self.camera_move_speed;
+        scene.camera_rotate_speed = self.camera_rotate_speed;
+
+        if self.black_holes.len() > MAX_BLACK_HOLES {
+            log::warn!("Scene has {} black holes, only the first {} are loaded", self.black_holes.len(), MAX_BLACK_HOLES);
+        }
+
+        scene.black_holes.clear();
+        self.black_holes.into_iter().for_each(|black_hole| { scene.black_holes.insert(physical(black_hole)); });
+
+        if self.wormholes.len() > MAX_WORMHOLES {
+            log::warn!("Scene has {} wormholes, only the first {} are loaded", self.wormholes.len(), MAX_WORMHOLES);
+        }
+
+        scene.wormholes.clear();
+        self.wormholes.into_iter().for_each(|wormhole| { scene.wormholes.insert(wormhole); });
+
+        if !self.materials.is_empty() {
+            if self.materials.len() > MAX_MATERIALS {
+                log::warn!("Scene has {} materials, only the first {} are loaded", self.materials.len(), MAX_MATERIALS);
+            }
+
+            scene.materials.clear();
+            self.materials.into_iter().for_each(|material| { scene.materials.insert(material); });
+        }
+
+        for placement in self.models {
+            let mut found = false;
+
+            for i in 0..scene.models.size() {
+                let Some(model) = scene.models.get_mut(i) else {
+                    continue;
+                };
+
+                if model.ident() == placement.ident {
+                    model.position = placement.position;
+                    model.rotation = placement.rotation;
+                    model.visible = placement.visible;
+                    found = true;
+                }
+            }
+
+            if !found {
+                log::warn!("Model {} is not loaded, its placement is skipped", placement.ident);
+            }
+        }
+
+        scene.timeline = self.timeline;
+
+        core.ray_details = self.ray_details;
+        core.fxaa_details = self.fxaa_details;
+        core.mix_details = self.mix_details;
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneFileError> {
+        let text = fs::read_to_string(path).map_err(SceneFileError::Io)?;
+
+        ron::from_str(&text).map_err(SceneFileError::Parse)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(SceneFileError::Write)?;
+
+        fs::write(path, text).map_err(SceneFileError::Io)
+    }
+}
+
+// hand edited files can ask for more spin and charge than a hole can have,
+// spin is kept and the charge brought down to a^2 + Q^2 <= 1
+fn physical(mut black_hole: BlackHole) -> BlackHole {
+    let spin = black_hole.spin.clamp(-1.0, 1.0);
+    // only Q^2 enters the metric, so either sign of charge is fine
+    let max_charge = (1.0 - spin * spin).sqrt();
+    let charge = black_hole.charge.clamp(-max_charge, max_charge);
+
+    if spin != black_hole.spin || charge != black_hole.charge {
+        log::warn!("Black hole spin {} and charge {} are past extremal, using {} and {}", black_hole.spin, black_hole.charge, spin, charge);
+    }
+
+    black_hole.spin = spin;
+    black_hole.charge = charge;
+
+    black_hole
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+
+    fn to_ron(file: &SceneFile) -> String {
+        ron::ser::to_string_pretty(file, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut file = SceneFile::default();
+
+        file.camera.position = Vector3::new(1.0, 2.0, -30.0);
+        file.black_holes[0].spin = 0.6;
+        file.black_holes[0].charge = 0.5;
+        file.black_holes.push(BlackHole::with_mass(2.0));
+        file.wormholes.push(Wormhole::new());
+        file.materials.push(Material::new([0.2, 0.4, 0.6, 1.0]));
+        file.models.push(ModelPlacement {
+            ident: "Lucy".into(),
+            position: Vector3::new(0.0, 1.0, 2.0),
+            rotation: Vector3::new(0.0, 0.5, 0.0),
+            visible: 0,
+        });
+        file.timeline.key_camera(&file.camera);
+        file.timeline.looping = false;
+
+        let text = to_ron(&file);
+        let loaded: SceneFile = ron::from_str(&text).unwrap();
+
+        assert_eq!(to_ron(&loaded), text);
+        assert_eq!(loaded.black_holes.len(), 2);
+        assert_eq!(loaded.black_holes[0].charge, 0.5);
+        assert_eq!(loaded.models[0].ident, "Lucy");
+        assert_eq!(loaded.timeline.camera_keyframes.len(), 1);
+        assert!(!loaded.timeline.looping);
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let loaded: SceneFile = ron::from_str("(camera_move_speed: 3.0)").unwrap();
+
+        assert_eq!(loaded.camera_move_speed, 3.0);
+        assert_eq!(loaded.black_holes.len(), 1);
+    }
+
+    #[test]
+    fn past_extremal_holes_are_clamped() {
+        let black_hole = physical(BlackHole { spin: 1.5, charge: 0.5, ..BlackHole::new() });
+
+        assert_eq!(black_hole.spin, 1.0);
+        assert_eq!(black_hole.charge, 0.0);
+
+        let black_hole = physical(BlackHole { spin: 0.6, charge: 0.9, ..BlackHole::new() });
+
+        assert!((black_hole.charge - 0.8).abs() < 1e-6);
+        assert!(black_hole.spin * black_hole.spin + black_hole.charge * black_hole.charge <= 1.0 + 1e-6);
+
+        let black_hole = physical(BlackHole { spin: 0.6, charge: -0.9, ..BlackHole::new() });
+
+        assert!((black_hole.charge + 0.8).abs() < 1e-6);
+
+        let black_hole = physical(BlackHole { spin: 0.6, charge: -0.5, ..BlackHole::new() });
+
+        assert_eq!(black_hole.charge, -0.5);
+    }
+}
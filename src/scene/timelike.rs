@@ -0,0 +1,243 @@
+use cgmath::{InnerSpace, Vector3};
+
+use super::blackhole::BlackHole;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GeodesicStart {
+    #[default]
+    CircularOrbit,
+    Plunge,
+    // velocities are set by hand
+    Custom,
+}
+
+impl From<GeodesicStart> for String {
+    fn from(value: GeodesicStart) -> Self {
+        match value {
+            GeodesicStart::CircularOrbit => "Circular Orbit".into(),
+            GeodesicStart::Plunge => "Plunge From Rest".into(),
+            GeodesicStart::Custom => "Custom".into(),
+        }
+    }
+}
+
+// orbit of a massive particle in the plane it starts in, r and phi about the
+// hole with r_dot = dr/dtau
+#[derive(Debug, Copy, Clone)]
+struct GeodesicState {
+    center: Vector3<f64>,
+    e1: Vector3<f64>,
+    e2: Vector3<f64>,
+    mass: f64,
+    energy: f64,
+    angular_momentum: f64,
+    r: f64,
+    r_dot: f64,
+    phi: f64,
+}
+
+impl GeodesicState {
+    fn position(&self) -> Vector3<f64> {
+        self.center + (self.e1 * self.phi.cos() + self.e2 * self.phi.sin()) * self.r
+    }
+
+    // velocity a static observer at the same place measures, in c
+    fn velocity(&self) -> Vector3<f64> {
+        let radial = self.e1 * self.phi.cos() + self.e2 * self.phi.sin();
+        let tangent = -self.e1 * self.phi.sin() + self.e2 * self.phi.cos();
+
+        let redshift = (1.0 - 2.0 * self.mass / self.r).max(0.0).sqrt();
+        let velocity = radial * self.r_dot / self.energy + tangent * self.angular_momentum * redshift / (self.r * self.energy);
+
+        // the last step before the horizon can overshoot
+        if velocity.magnitude() > 0.99 {
+            return velocity.normalize() * 0.99;
+        }
+
+        velocity
+    }
+
+    fn derivative(&self, r: f64, r_dot: f64) -> (f64, f64, f64) {
+        let l2 = self.angular_momentum * self.angular_momentum;
+        let r_ddot = -self.mass / (r * r) + l2 / (r * r * r) - 3.0 * self.mass * l2 / (r * r * r * r);
+
+        (r_dot, r_ddot, self.angular_momentum / (r * r))
+    }
+
+    // rk4 in proper time, returns the coordinate time that passed
+    fn step(&mut self, h: f64) -> f64 {
+        let (k1_r, k1_v, k1_p) = self.derivative(self.r, self.r_dot);
+        let (k2_r, k2_v, k2_p) = self.derivative(self.r + 0.5 * h * k1_r, self.r_dot + 0.5 * h * k1_v);
+        let (k3_r, k3_v, k3_p) = self.derivative(self.r + 0.5 * h * k2_r, self.r_dot + 0.5 * h * k2_v);
+        let (k4_r, k4_v, k4_p) = self.derivative(self.r + h * k3_r, self.r_dot + h * k3_v);
+
+        let r_prev = self.r;
+
+        self.r += h / 6.0 * (k1_r + 2.0 * k2_r + 2.0 * k3_r + k4_r);
+        self.r_dot += h / 6.0 * (k1_v + 2.0 * k2_v + 2.0 * k3_v + k4_v);
+        self.phi += h / 6.0 * (k1_p + 2.0 * k2_p + 2.0 * k3_p + k4_p);
+
+        // dt/dtau = E / (1 - 2M/r)
+        let r_mid = 0.5 * (r_prev + self.r);
+
+        h * self.energy / (1.0 - 2.0 * self.mass / r_mid)
+    }
+}
+
+// camera controller that moves along a timelike geodesic of the nearest hole,
+// using its schwarzschild solution so spin is ignored. Only the launch
+// settings are saved, it launches again after loading
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TimelikeGeodesic {
+    pub start: GeodesicStart,
+    // velocities a static observer measures at launch, in c
+    pub radial_velocity: f32,
+    pub tangential_velocity: f32,
+    pub paused: bool,
+    #[serde(skip)]
+    pub proper_time: f64,
+    #[serde(skip)]
+    pub coordinate_time: f64,
+    #[serde(skip)]
+    pub reached_horizon: bool,
+    #[serde(skip)]
+    launch_position: Option<Vector3<f32>>,
+    #[serde(skip)]
+    state: Option<GeodesicState>,
+}
+
+impl TimelikeGeodesic {
+    pub fn new() -> Self {
+        Self {
+            start: GeodesicStart::default(),
+            radial_velocity: 0.0,
+            tangential_velocity: 0.3,
+            paused: false,
+            proper_time: 0.0,
+            coordinate_time: 0.0,
+            reached_horizon: false,
+            launch_position: None,
+            state: None,
+        }
+    }
+
+    // launches again from wherever the camera is on the next update
+    pub fn reset(&mut self) {
+        self.state = None;
+        self.launch_position = None;
+        self.reached_horizon = false;
+    }
+
+    pub fn restart(&mut self, position: &mut Vector3<f32>) {
+        if let Some(launch_position) = self.launch_position {
+            *position = launch_position;
+        }
+
+        self.reset();
+    }
+
+    pub fn radius(&self) -> Option<f64> {
+        self.state.map(|state| state.r / state.mass)
+    }
+
+    fn launch(&mut self, position: Vector3<f32>, black_hole: &BlackHole) {
+        self.proper_time = 0.0;
+        self.coordinate_time = 0.0;
+        self.reached_horizon = false;
+        self.launch_position = Some(position);
+
+        let mass = black_hole.mass as f64;
+        let center = black_hole.position.cast::<f64>().unwrap();
+        let offset = position.cast::<f64>().unwrap() - center;
+        let r = offset.magnitude();
+
+        if r <= 2.0 * mass {
+            self.reached_horizon = true;
+            return;
+        }
+
+        let e1 = offset / r;
+
+        // prograde with the disk, which orbits about -normal
+        let normal = -black_hole.disk_normal().cast::<f64>().unwrap();
+        let mut e2 = normal.cross(e1);
+
+        if e2.magnitude2() < 1e-8 {
+            e2 = e1.cross(Vector3::unit_x());
+
+            if e2.magnitude2() < 1e-8 {
+                e2 = e1.cross(Vector3::unit_y());
+            }
+        }
+
+        let e2 = e2.normalize();
+
+        let (radial_velocity, tangential_velocity) = match self.start {
+            GeodesicStart::CircularOrbit if r > 3.0 * mass => (0.0, (mass / (r - 2.0 * mass)).sqrt()),
+            GeodesicStart::CircularOrbit => (0.0, 0.0),
+            GeodesicStart::Plunge => (0.0, 0.0),
+            GeodesicStart::Custom => (self.radial_velocity as f64, self.tangential_velocity as f64),
+        };
+
+        let speed2 = (radial_velocity * radial_velocity + tangential_velocity * tangential_velocity).min(0.9999);
+        let gamma = 1.0 / (1.0 - speed2).sqrt();
+        let redshift = (1.0 - 2.0 * mass / r).sqrt();
+
+        self.state = Some(GeodesicState {
+            center,
+            e1,
+            e2,
+            mass,
+            energy: gamma * redshift,
+            angular_momentum: gamma * tangential_velocity * r,
+            r,
+            r_dot: gamma * radial_velocity * redshift,
+            phi: 0.0,
+        });
+    }
+
+    // advances by proper_time_step and moves the camera with it
+    pub fn update(&mut self, position: &mut Vector3<f32>, velocity: &mut Vector3<f32>, black_hole: &BlackHole, proper_time_step: f64) {
+        if self.state.is_none() && !self.reached_horizon {
+            self.launch(*position, black_hole);
+        }
+
+        let Some(mut state) = self.state else {
+            return;
+        };
+
+        if self.paused || self.reached_horizon {
+            return;
+        }
+
+        let horizon = 2.0 * state.mass;
+        let mut remaining = proper_time_step;
+
+        while remaining > 0.0 {
+            // a few hundred steps per orbit
+            let h = remaining.min(0.03 * (state.r * state.r * state.r / state.mass).sqrt());
+
+            self.coordinate_time += state.step(h);
+            self.proper_time += h;
+            remaining -= h;
+
+            if state.r <= 1.01 * horizon {
+                state.r = state.r.max(1.01 * horizon);
+                self.reached_horizon = true;
+                break;
+            }
+        }
+
+        self.state = Some(state);
+
+        *position = state.position().cast::<f32>().unwrap();
+        *velocity = state.velocity().cast::<f32>().unwrap();
+    }
+}
+
+impl Default for TimelikeGeodesic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,51 @@
+use cgmath::{Vector3, Zero};
+
+use crate::renderer::array_buffer::{ArrayBuffer, ArrayBufferUniform};
+
+pub const MAX_WORMHOLES: usize = 2;
+
+// ellis wormhole, r^2 = l^2 + b^2 with b the throat radius. The far side is
+// empty apart from its own sky
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Wormhole {
+    pub position: Vector3<f32>,
+    pub throat_radius: f32,
+    // rays are traced straight outside of this
+    pub relativity_sphere_radius: f32,
+}
+
+impl Wormhole {
+    pub fn new() -> Self {
+        Self {
+            position: Vector3::zero(),
+            throat_radius: 1.0,
+            relativity_sphere_radius: 20.0,
+        }
+    }
+}
+
+impl Default for Wormhole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct WormholeUniform {
+    position: [f32; 3],
+    throat_radius: f32,
+    relativity_sphere_radius: f32,
+    pad1: [f32; 3],
+}
+
+impl ArrayBufferUniform<Wormhole> for WormholeUniform {
+    fn update(&mut self, wormhole: &Wormhole) {
+        self.position = wormhole.position.into();
+        self.throat_radius = wormhole.throat_radius;
+        self.relativity_sphere_radius = wormhole.relativity_sphere_radius.max(wormhole.throat_radius * 1.01);
+    }
+}
+
+pub type WormholeArrayBuffer = ArrayBuffer<MAX_WORMHOLES, Wormhole, WormholeUniform>;
@@ -1,21 +1,57 @@
-use crate::scene::Scene;
+use cgmath::Vector3;
+
+use crate::scene::{blackhole::{Beaming, BlackHole, MAX_BLACK_HOLES}, metric::MetricKind, Scene};
 
 pub struct BlackHoleSettings {
     visible: bool,
+    selected: usize,
 }
 
 impl BlackHoleSettings {
     pub fn new() -> Self {
         Self {
             visible: false,
+            selected: 0,
         }
     }
 
-    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene) {
+    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene, metric: MetricKind) {
         egui::Window::new("Black Hole Settings")
             .open(&mut self.visible)
             .frame(egui::Frame::window(&egui::Style::default()))
             .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for i in 0..scene.black_holes.size() {
+                        ui.selectable_value(&mut self.selected, i, format!("Black Hole {}", i));
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let can_add = scene.black_holes.size() < MAX_BLACK_HOLES;
+
+                    if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                        let mut black_hole = BlackHole::new();
+                        black_hole.position = Vector3::new(0.0, 0.0, 40.0 * scene.black_holes.size() as f32);
+
+                        scene.black_holes.insert(black_hole);
+                        self.selected = scene.black_holes.size() - 1;
+                    }
+
+                    let can_remove = scene.black_holes.size() > 1;
+
+                    if ui.add_enabled(can_remove, egui::Button::new("Remove")).clicked() {
+                        scene.remove_black_hole(self.selected);
+                    }
+                });
+
+                self.selected = self.selected.min(scene.black_holes.size().saturating_sub(1));
+
+                let Some(black_hole) = scene.black_holes.get_mut(self.selected) else {
+                    return;
+                };
+
+                ui.separator();
+
                 egui::Grid::new("black_hole_settings_grid")
                     .num_columns(2)
                     .spacing([40.0, 4.0])
@@ -23,52 +59,211 @@ impl BlackHoleSettings {
                     .show(ui, |ui| {
                         ui.label("Position:");
                         ui.columns(3, |ui| {
-                            ui[0].add(egui::DragValue::new(&mut scene.black_hole.position.x).speed(0.01));
-                            ui[1].add(egui::DragValue::new(&mut scene.black_hole.position.y).speed(0.01));
-                            ui[2].add(egui::DragValue::new(&mut scene.black_hole.position.z).speed(0.01));
+                            ui[0].add(egui::DragValue::new(&mut black_hole.position.x).speed(0.01));
+                            ui[1].add(egui::DragValue::new(&mut black_hole.position.y).speed(0.01));
+                            ui[2].add(egui::DragValue::new(&mut black_hole.position.z).speed(0.01));
                         });
                         ui.end_row(); 
 
                         ui.label("Disk Rotation:");
                         ui.columns(3, |ui| {
-                            ui[0].add(egui::DragValue::new(&mut scene.black_hole.accretion_disk_rotation.x).speed(0.01));
-                            ui[1].add(egui::DragValue::new(&mut scene.black_hole.accretion_disk_rotation.y).speed(0.01));
-                            ui[2].add(egui::DragValue::new(&mut scene.black_hole.accretion_disk_rotation.z).speed(0.01));
+                            ui[0].add(egui::DragValue::new(&mut black_hole.accretion_disk_rotation.x).speed(0.01));
+                            ui[1].add(egui::DragValue::new(&mut black_hole.accretion_disk_rotation.y).speed(0.01));
+                            ui[2].add(egui::DragValue::new(&mut black_hole.accretion_disk_rotation.z).speed(0.01));
                         });
                         ui.end_row(); 
 
                         ui.label("Rotation Speed:");
-                        ui.add(egui::DragValue::new(&mut scene.black_hole.rotation_speed).clamp_range(0.0..=10.0).speed(0.01));
+                        ui.add(egui::DragValue::new(&mut black_hole.rotation_speed).clamp_range(0.0..=10.0).speed(0.01));
+                        ui.end_row(); 
+
+                        ui.label("Mass:");
+                        let mut mass = black_hole.mass;
+                        if ui.add(egui::DragValue::new(&mut mass).clamp_range(0.01..=100.0).speed(0.01)).changed() {
+                            black_hole.set_mass(mass);
+                        }
+                        ui.end_row(); 
+
+                        ui.label("Schwarzschild Radius:");
+                        ui.label(format!("{:.3}", black_hole.schwarzschild_radius()));
                         ui.end_row(); 
 
-                        ui.label("Disk Inner Radius:");
-                        ui.add(egui::DragValue::new(&mut scene.black_hole.accretion_disk_inner).clamp_range(0.0..=1000.0).speed(0.01));
+                        // a^2 + Q^2 <= M^2 keeps the horizon
+                        let max_spin = (1.0 - black_hole.charge * black_hole.charge).max(0.0).sqrt();
+
+                        ui.label("Spin (a/M):");
+                        ui.add_enabled(metric.uses_spin(), egui::DragValue::new(&mut black_hole.spin).clamp_range(-max_spin..=max_spin).speed(0.01));
+                        ui.end_row(); 
+
+                        let max_charge = (1.0 - black_hole.spin * black_hole.spin).max(0.0).sqrt();
+
+                        ui.label("Charge (Q/M):");
+                        ui.add_enabled(metric.uses_charge(), egui::DragValue::new(&mut black_hole.charge).clamp_range(-max_charge..=max_charge).speed(0.01));
+                        ui.end_row(); 
+
+                        // what the selected metric draws
+                        let shape = black_hole.for_metric(metric);
+
+                        ui.label("Horizon Radius:");
+                        ui.label(format!("{:.3}", shape.horizon_radius()));
                         ui.end_row(); 
 
+                        ui.label("Photon Sphere Radius:");
+                        ui.label(format!("{:.3}", shape.photon_sphere_radius()));
+                        ui.end_row(); 
+
+                        ui.label("ISCO Radius:");
+                        ui.label(format!("{:.3}", shape.isco_radius()));
+                        ui.end_row(); 
+
+                        ui.label("Disk Inner At ISCO");
+                        let mut disk_follows_isco = black_hole.disk_follows_isco != 0;
+                        ui.checkbox(&mut disk_follows_isco, "checked");
+                        black_hole.disk_follows_isco = disk_follows_isco as i32; 
+                        ui.end_row(); 
+
+                        if black_hole.disk_follows_isco == 0 {
+                            ui.label("Disk Inner Radius:");
+                            ui.add(egui::DragValue::new(&mut black_hole.accretion_disk_inner).clamp_range(0.0..=1000.0).speed(0.01));
+                            ui.end_row(); 
+                        }
+
                         ui.label("Disk Outer Radius:");
-                        ui.add(egui::DragValue::new(&mut scene.black_hole.accretion_disk_outer).clamp_range(0.0..=1000.0).speed(0.01));
+                        ui.add(egui::DragValue::new(&mut black_hole.accretion_disk_outer).clamp_range(0.0..=1000.0).speed(0.01));
                         ui.end_row(); 
 
                         ui.label("Relativity Radius:");
-                        ui.add(egui::DragValue::new(&mut scene.black_hole.relativity_sphere_radius).clamp_range(0.0..=1000.0).speed(0.01));
+                        ui.add(egui::DragValue::new(&mut black_hole.relativity_sphere_radius).clamp_range(0.0..=1000.0).speed(0.01));
                         ui.end_row(); 
 
                         ui.label("Relativity Feather:");
-                        ui.add(egui::DragValue::new(&mut scene.black_hole.feather_amount).clamp_range(0.0..=1.0).speed(0.01));
+                        ui.add(egui::DragValue::new(&mut black_hole.feather_amount).clamp_range(0.0..=1.0).speed(0.01));
                         ui.end_row(); 
 
-                        ui.label("Show Disk Texture");
-                        let mut show_disk_texture = scene.black_hole.show_disk_texture != 0;
-                        ui.checkbox(&mut show_disk_texture, "checked");
-                        scene.black_hole.show_disk_texture = show_disk_texture as i32; 
+                        ui.label("Volumetric Disk");
+                        let mut volumetric_disk = black_hole.volumetric_disk != 0;
+                        ui.checkbox(&mut volumetric_disk, "checked");
+                        black_hole.volumetric_disk = volumetric_disk as i32; 
                         ui.end_row(); 
 
+                        if black_hole.volumetric_disk != 0 {
+                            ui.label("Disk Scale Height (H/r):");
+                            ui.add(egui::DragValue::new(&mut black_hole.disk_scale_height).clamp_range(0.001..=1.0).speed(0.001));
+                            ui.end_row(); 
+
+                            ui.label("Disk Density:");
+                            ui.add(egui::DragValue::new(&mut black_hole.disk_density).clamp_range(0.0..=100.0).speed(0.01));
+                            ui.end_row(); 
+
+                            ui.label("Disk Noise Scale:");
+                            ui.add(egui::DragValue::new(&mut black_hole.disk_noise_scale).clamp_range(0.01..=100.0).speed(0.01));
+                            ui.end_row(); 
+                        } else {
+                            ui.label("Show Disk Texture");
+                            let mut show_disk_texture = black_hole.show_disk_texture != 0;
+                            ui.checkbox(&mut show_disk_texture, "checked");
+                            black_hole.show_disk_texture = show_disk_texture as i32; 
+                            ui.end_row(); 
+                        }
+
+                        ui.label("Jets");
+                        let mut show_jets = black_hole.show_jets != 0;
+                        ui.checkbox(&mut show_jets, "checked");
+                        black_hole.show_jets = show_jets as i32; 
+                        ui.end_row(); 
+
+                        if black_hole.show_jets != 0 {
+                            ui.label("Jet Length:");
+                            ui.add(egui::DragValue::new(&mut black_hole.jet_length).clamp_range(0.0..=black_hole.relativity_sphere_radius).speed(0.01));
+                            ui.end_row(); 
+
+                            ui.label("Jet Opening Angle:");
+                            ui.add(egui::DragValue::new(&mut black_hole.jet_opening_angle).clamp_range(0.0..=1.5).speed(0.005));
+                            ui.end_row(); 
+
+                            ui.label("Jet Brightness:");
+                            ui.add(egui::DragValue::new(&mut black_hole.jet_brightness).clamp_range(0.0..=100.0).speed(0.01));
+                            ui.end_row(); 
+
+                            ui.label("Jet Lorentz Factor:");
+                            ui.add(egui::DragValue::new(&mut black_hole.jet_lorentz_factor).clamp_range(1.0..=50.0).speed(0.01));
+                            ui.end_row(); 
+                        }
+
+                        ui.label("Temperature Profile");
+                        let mut temperature_profile = black_hole.temperature_profile != 0;
+                        ui.checkbox(&mut temperature_profile, "checked");
+                        black_hole.temperature_profile = temperature_profile as i32; 
+                        ui.end_row(); 
+
+                        if black_hole.temperature_profile != 0 {
+                            ui.label("Peak Temperature (K):");
+                            ui.add(egui::DragValue::new(&mut black_hole.disk_temperature).clamp_range(1000.0..=1000000.0).speed(100.0));
+                            ui.end_row(); 
+
+                            ui.label("Accretion Rate:");
+                            ui.add(egui::DragValue::new(&mut black_hole.accretion_rate).clamp_range(0.0..=100.0).speed(0.01));
+                            ui.end_row(); 
+                        }
+
+                        ui.label("Beaming:");
+
+                        let options = [
+                            Beaming::Off,
+                            Beaming::Line,
+                            Beaming::Continuum,
+                        ];
+
+                        egui::ComboBox::from_id_source("black_hole_beaming")
+                            .selected_text(String::from(black_hole.beaming))
+                            .show_ui(ui, |ui| {
+                                for option in options {
+                                    ui.selectable_value(
+                                        &mut black_hole.beaming,
+                                        option,
+                                        String::from(option)
+                                    );
+                                }
+                            });
+
+                        ui.end_row(); 
+
+                        if black_hole.beaming != Beaming::Off {
+                            ui.label("Beaming Exaggeration:");
+                            ui.add(egui::DragValue::new(&mut black_hole.beaming_exaggeration).clamp_range(0.0..=10.0).speed(0.01));
+                            ui.end_row(); 
+                        }
+
                         ui.label("Redshift");
-                        let mut show_red_shift = scene.black_hole.show_red_shift != 0;
+                        let mut show_red_shift = black_hole.show_red_shift != 0;
                         ui.checkbox(&mut show_red_shift, "checked");
-                        scene.black_hole.show_red_shift = show_red_shift as i32; 
+                        black_hole.show_red_shift = show_red_shift as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Show Photon Sphere");
+                        let mut show_photon_sphere = black_hole.show_photon_sphere != 0;
+                        ui.checkbox(&mut show_photon_sphere, "checked");
+                        black_hole.show_photon_sphere = show_photon_sphere as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Show ISCO");
+                        let mut show_isco = black_hole.show_isco != 0;
+                        ui.checkbox(&mut show_isco, "checked");
+                        black_hole.show_isco = show_isco as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Show Horizon");
+                        let mut show_horizon = black_hole.show_horizon != 0;
+                        ui.checkbox(&mut show_horizon, "checked");
+                        black_hole.show_horizon = show_horizon as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Show Relativity Sphere");
+                        let mut show_relativity_sphere = black_hole.show_relativity_sphere != 0;
+                        ui.checkbox(&mut show_relativity_sphere, "checked");
+                        black_hole.show_relativity_sphere = show_relativity_sphere as i32; 
                         ui.end_row(); 
-                    })
+                    });
             });
     }
 
@@ -1,6 +1,8 @@
 use std::f32::consts::PI;
 
-use crate::scene::Scene;
+use cgmath::InnerSpace;
+
+use crate::scene::{camera::ObserverMotion, timelike::GeodesicStart, Scene};
 
 pub struct CameraSettings {
     visible: bool,
@@ -50,6 +52,148 @@ impl CameraSettings {
                         ui.label("Rotate Speed (rads/s):");
                         ui.add(egui::DragValue::new(&mut scene.camera_rotate_speed).clamp_range(0.0..=2.0).speed(0.01));
                         ui.end_row(); 
+
+                        ui.label("Motion:");
+
+                        let options = [
+                            ObserverMotion::Static,
+                            ObserverMotion::Moving,
+                            ObserverMotion::CircularOrbit,
+                            ObserverMotion::FreeFall,
+                            ObserverMotion::Geodesic,
+                        ];
+
+                        egui::ComboBox::from_id_source("camera_motion")
+                            .selected_text(String::from(scene.camera.motion))
+                            .show_ui(ui, |ui| {
+                                for option in options {
+                                    ui.selectable_value(
+                                        &mut scene.camera.motion,
+                                        option,
+                                        String::from(option)
+                                    );
+                                }
+                            });
+
+                        ui.end_row(); 
+
+                        match scene.camera.motion {
+                            ObserverMotion::Static => {},
+                            ObserverMotion::Moving => {
+                                ui.label("Velocity (c):");
+                                ui.columns(3, |ui| {
+                                    ui[0].add(egui::DragValue::new(&mut scene.camera.velocity.x).clamp_range(-0.99..=0.99).speed(0.005));
+                                    ui[1].add(egui::DragValue::new(&mut scene.camera.velocity.y).clamp_range(-0.99..=0.99).speed(0.005));
+                                    ui[2].add(egui::DragValue::new(&mut scene.camera.velocity.z).clamp_range(-0.99..=0.99).speed(0.005));
+                                });
+                                ui.end_row(); 
+
+                                // keep it below c
+                                let speed = scene.camera.velocity.magnitude();
+
+                                if speed > 0.99 {
+                                    scene.camera.velocity *= 0.99 / speed;
+                                }
+                            },
+                            ObserverMotion::CircularOrbit | ObserverMotion::FreeFall => {
+                                ui.label("Motion Speed (M/s):");
+                                ui.add(egui::DragValue::new(&mut scene.camera.motion_speed).clamp_range(0.0..=100.0).speed(0.05));
+                                ui.end_row(); 
+
+                                ui.label("Speed (c):");
+                                ui.label(format!("{:.3}", scene.camera.velocity.magnitude()));
+                                ui.end_row(); 
+                            },
+                            ObserverMotion::Geodesic => {
+                                let geodesic = &mut scene.camera.geodesic;
+
+                                ui.label("Start:");
+
+                                let options = [
+                                    GeodesicStart::CircularOrbit,
+                                    GeodesicStart::Plunge,
+                                    GeodesicStart::Custom,
+                                ];
+
+                                egui::ComboBox::from_id_source("camera_geodesic_start")
+                                    .selected_text(String::from(geodesic.start))
+                                    .show_ui(ui, |ui| {
+                                        for option in options {
+                                            ui.selectable_value(
+                                                &mut geodesic.start,
+                                                option,
+                                                String::from(option)
+                                            );
+                                        }
+                                    });
+
+                                ui.end_row(); 
+
+                                if geodesic.start == GeodesicStart::Custom {
+                                    ui.label("Radial Velocity (c):");
+                                    ui.add(egui::DragValue::new(&mut geodesic.radial_velocity).clamp_range(-0.99..=0.99).speed(0.005));
+                                    ui.end_row(); 
+
+                                    ui.label("Tangential Velocity (c):");
+                                    ui.add(egui::DragValue::new(&mut geodesic.tangential_velocity).clamp_range(-0.99..=0.99).speed(0.005));
+                                    ui.end_row(); 
+                                }
+
+                                ui.label("Proper Time Speed (M/s):");
+                                ui.add(egui::DragValue::new(&mut scene.camera.motion_speed).clamp_range(0.0..=100.0).speed(0.05));
+                                ui.end_row(); 
+
+                                ui.label("Launch:");
+                                ui.horizontal(|ui| {
+                                    if ui.button("From Here").clicked() {
+                                        scene.camera.geodesic.reset();
+                                    }
+
+                                    if ui.button("Restart").clicked() {
+                                        scene.camera.geodesic.restart(&mut scene.camera.position);
+                                    }
+                                });
+                                ui.end_row(); 
+
+                                let geodesic = &mut scene.camera.geodesic;
+
+                                ui.label("Pause:");
+                                ui.checkbox(&mut geodesic.paused, "checked");
+                                ui.end_row(); 
+
+                                ui.label("Proper Time (M):");
+                                ui.label(format!("{:.2}", geodesic.proper_time));
+                                ui.end_row(); 
+
+                                ui.label("Coordinate Time (M):");
+                                ui.label(format!("{:.2}", geodesic.coordinate_time));
+                                ui.end_row(); 
+
+                                ui.label("Radius (M):");
+                                match geodesic.radius() {
+                                    Some(radius) if geodesic.reached_horizon => ui.label(format!("{:.2} (horizon)", radius)),
+                                    Some(radius) => ui.label(format!("{:.2}", radius)),
+                                    None => ui.label("-"),
+                                };
+                                ui.end_row(); 
+
+                                ui.label("Speed (c):");
+                                ui.label(format!("{:.3}", scene.camera.velocity.magnitude()));
+                                ui.end_row(); 
+                            },
+                        }
+
+                        ui.label("Aberration:");
+                        let mut aberration_bool = scene.camera.aberration != 0;
+                        ui.checkbox(&mut aberration_bool, "checked");
+                        scene.camera.aberration = aberration_bool as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Doppler Shift:");
+                        let mut doppler_bool = scene.camera.doppler != 0;
+                        ui.checkbox(&mut doppler_bool, "checked");
+                        scene.camera.doppler = doppler_bool as i32; 
+                        ui.end_row(); 
                     })
             });
     }
@@ -2,12 +2,15 @@ pub mod camera_settings;
 pub mod model_settings;
 pub mod black_hole_settings;
 pub mod render_settings;
+pub mod wormhole_settings;
+pub mod timeline_settings;
+pub mod recording_settings;
 
 use winit::window::{Fullscreen, Window};
 
-use crate::{renderer::Renderer, scene::Scene};
+use crate::{renderer::Renderer, scene::{scene_file::SceneFile, Scene}};
 
-use self::{black_hole_settings::BlackHoleSettings, camera_settings::CameraSettings, model_settings::ModelSettings, render_settings::RendererSettings};
+use self::{black_hole_settings::BlackHoleSettings, camera_settings::CameraSettings, model_settings::ModelSettings, recording_settings::RecordingSettings, render_settings::RendererSettings, timeline_settings::TimelineSettings, wormhole_settings::WormholeSettings};
 
 pub struct UI {
     egui_state: egui_winit::State,
@@ -19,6 +22,9 @@ pub struct UI {
     model_settings: ModelSettings,
     black_hole_settings: BlackHoleSettings,
     render_settings: RendererSettings,
+    wormhole_settings: WormholeSettings,
+    timeline_settings: TimelineSettings,
+    recording_settings: RecordingSettings,
 }
 
 impl UI {
@@ -38,6 +44,9 @@ impl UI {
         let model_settings = ModelSettings::new();
         let black_hole_settings = BlackHoleSettings::new();
         let render_settings = RendererSettings::new();
+        let wormhole_settings = WormholeSettings::new();
+        let timeline_settings = TimelineSettings::new();
+        let recording_settings = RecordingSettings::new();
 
         Self {
             egui_state,
@@ -49,6 +58,9 @@ impl UI {
             model_settings,
             black_hole_settings,
             render_settings,
+            wormhole_settings,
+            timeline_settings,
+            recording_settings,
         }
     }
 
@@ -88,8 +100,11 @@ impl UI {
         let egui_full_output = ctx.run(raw_input, |egui_ctx| {
             self.camera_settings.ui(egui_ctx, scene);
             self.model_settings.ui(egui_ctx, scene);
-            self.black_hole_settings.ui(egui_ctx, scene);
+            self.black_hole_settings.ui(egui_ctx, scene, renderer.core.ray_details.metric);
             self.render_settings.ui(egui_ctx, renderer);
+            self.wormhole_settings.ui(egui_ctx, scene);
+            self.timeline_settings.ui(egui_ctx, scene);
+            self.recording_settings.ui(egui_ctx, scene, renderer);
 
             egui::TopBottomPanel::top("menu_bar").show(egui_ctx, |ui| {
                 egui::menu::bar(ui, |ui| {
@@ -104,6 +119,37 @@ impl UI {
 
                             renderer.save = file.map(|file| file.to_str().unwrap().into());
                         }
+
+                        if ui.button("Open Scene").clicked() {
+                            let path = std::env::current_dir().unwrap();
+
+                            let file = rfd::FileDialog::new()
+                                .add_filter("scene", &["ron"])
+                                .set_directory(path)
+                                .pick_file();
+
+                            if let Some(file) = file {
+                                match SceneFile::load(&file) {
+                                    Ok(scene_file) => scene_file.apply(scene, &mut renderer.core),
+                                    Err(error) => log::error!("Failed to open scene {}: {}", file.display(), error),
+                                }
+                            }
+                        }
+
+                        if ui.button("Save Scene").clicked() {
+                            let path = std::env::current_dir().unwrap();
+
+                            let file = rfd::FileDialog::new()
+                                .add_filter("scene", &["ron"])
+                                .set_directory(path)
+                                .save_file();
+
+                            if let Some(file) = file {
+                                if let Err(error) = SceneFile::capture(scene, &renderer.core).save(&file) {
+                                    log::error!("Failed to save scene {}: {}", file.display(), error);
+                                }
+                            }
+                        }
                     });
 
                     if ui.input(|i| i.key_pressed(egui::Key::F11)) {
@@ -145,8 +191,14 @@ impl UI {
                             self.camera_settings.show();
                         } else if ui.button("Black Hole Settings").clicked() {
                             self.black_hole_settings.show();
+                        } else if ui.button("Wormhole Settings").clicked() {
+                            self.wormhole_settings.show();
                         } else if ui.button("Render Settings").clicked() {
                             self.render_settings.show();
+                        } else if ui.button("Timeline").clicked() {
+                            self.timeline_settings.show();
+                        } else if ui.button("Recording").clicked() {
+                            self.recording_settings.show();
                         }
                     });
 
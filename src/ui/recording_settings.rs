@@ -0,0 +1,122 @@
+use crate::{renderer::{recording::{Recording, RecordingFormat}, Renderer}, scene::Scene};
+
+pub struct RecordingSettings {
+    visible: bool,
+    format: RecordingFormat,
+    frame_count: u32,
+    fps: u32,
+}
+
+impl RecordingSettings {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            format: RecordingFormat::PngSequence,
+            frame_count: 120,
+            fps: 30,
+        }
+    }
+
+    fn start(&self, scene: &mut Scene, renderer: &mut Renderer) {
+        let path = std::env::current_dir().unwrap();
+
+        let file = rfd::FileDialog::new()
+            .add_filter(String::from(self.format), &[self.format.extension()])
+            .set_directory(path)
+            .save_file();
+
+        let Some(file) = file else {
+            return;
+        };
+
+        renderer.recording = Some(Recording::new(self.format, file.to_str().unwrap().into(), self.frame_count, self.fps));
+
+        // every recording starts from the beginning of the timeline
+        scene.rewind();
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene, renderer: &mut Renderer) {
+        let mut visible = self.visible;
+
+        egui::Window::new("Recording")
+            .open(&mut visible)
+            .frame(egui::Frame::window(&egui::Style::default()))
+            .show(ctx, |ui| {
+                let recording = renderer.recording.is_some();
+
+                ui.add_enabled_ui(!recording, |ui| {
+                    egui::Grid::new("recording_settings_grid")
+                        .num_columns(2)
+                        .spacing([40.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Format:");
+
+                            let options = [
+                                RecordingFormat::PngSequence,
+                                RecordingFormat::Y4m,
+                            ];
+
+                            egui::ComboBox::from_id_source("recording_format")
+                                .selected_text(String::from(self.format))
+                                .show_ui(ui, |ui| {
+                                    for option in options {
+                                        ui.selectable_value(
+                                            &mut self.format,
+                                            option,
+                                            String::from(option)
+                                        );
+                                    }
+                                });
+
+                            ui.end_row();
+
+                            ui.label("Frames:");
+                            ui.add(egui::DragValue::new(&mut self.frame_count).clamp_range(1..=100000));
+                            ui.end_row();
+
+                            ui.label("FPS:");
+                            ui.add(egui::DragValue::new(&mut self.fps).clamp_range(1..=240));
+                            ui.end_row();
+
+                            ui.label("Length:");
+                            ui.label(format!("{:.2} s", self.frame_count as f32 / self.fps as f32));
+                            ui.end_row();
+                        });
+                });
+
+                ui.separator();
+
+                if let Some(recording) = &mut renderer.recording {
+                    let mut stop = false;
+
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Frame {} / {}", recording.frame(), recording.frame_count));
+                        stop = ui.button("Stop").clicked();
+                    });
+
+                    if stop {
+                        if let Err(error) = recording.finish() {
+                            log::error!("Failed to finish recording {}: {}", recording.path, error);
+                        }
+
+                        renderer.recording = None;
+                    }
+                } else if ui.button("Record").clicked() {
+                    self.start(scene, renderer);
+                }
+            });
+
+        self.visible = visible;
+    }
+
+    pub fn show(&mut self) {
+        self.visible = true;
+    }
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
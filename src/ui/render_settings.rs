@@ -2,7 +2,7 @@ use std::f32::consts::PI;
 
 use wgpu::PresentMode;
 
-use crate::renderer::{pipelines::fxaa_pipline::{EdgeThresholdMax, EdgeThresholdMin}, Renderer};
+use crate::{renderer::{pipelines::{fxaa_pipline::{EdgeThresholdMax, EdgeThresholdMin}, ray_pipeline::IntegrationMethod}, Renderer}, scene::metric::MetricKind};
 
 fn present_mode_to_string(mode: PresentMode) -> String {
     match mode {
@@ -32,39 +32,92 @@ impl RendererSettings {
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
+                ui.label("Metric:");
+
+                let options = [
+                    MetricKind::Schwarzschild,
+                    MetricKind::Kerr,
+                    MetricKind::ReissnerNordstrom,
+                    MetricKind::KerrNewman,
+                ];
+
+                egui::ComboBox::from_id_source("renderer_metric")
+                    .selected_text(String::from(renderer.core.ray_details.metric))
+                    .show_ui(ui, |ui| {
+                        for option in options {
+                            ui.selectable_value(
+                                &mut renderer.core.ray_details.metric,
+                                option,
+                                String::from(option)
+                            );
+                        }
+                    });
+
+                ui.end_row(); 
+
                 ui.label("OED Method:");
 
-                let selected_text = match renderer.ray_details.integration_method {
-                    0 => "Euler",
-                    1 => "Runge Kutta",
-                    _ => ""
-                };
+                let options = [
+                    IntegrationMethod::Euler,
+                    IntegrationMethod::CashKarp,
+                    IntegrationMethod::RK4,
+                    IntegrationMethod::DormandPrince,
+                    IntegrationMethod::Verlet,
+                ];
 
                 egui::ComboBox::from_id_source("renderer_use_rk")
-                    .selected_text(selected_text)
+                    .selected_text(String::from(renderer.core.ray_details.integration_method))
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut renderer.ray_details.integration_method,
-                            0,
-                            "Euler"
-                        );
-
-                        ui.selectable_value(
-                            &mut renderer.ray_details.integration_method,
-                            1,
-                            "Runge Kutta"
-                        );
+                        for option in options {
+                            ui.selectable_value(
+                                &mut renderer.core.ray_details.integration_method,
+                                option,
+                                String::from(option)
+                            );
+                        }
                     });
 
                 ui.end_row(); 
 
-                ui.label("Step size");
-                ui.add(egui::DragValue::new(&mut renderer.ray_details.step_size).speed(0.005).clamp_range(0.005..=1.0));
+                if renderer.core.ray_details.integration_method.is_adaptive() {
+                    ui.label("Initial Step size");
+                } else {
+                    ui.label("Step size");
+                }
+                ui.add(egui::DragValue::new(&mut renderer.core.ray_details.step_size).speed(0.005).clamp_range(0.005..=1.0));
                 ui.end_row(); 
 
                 ui.label("Max Iterations");
-                ui.add(egui::DragValue::new(&mut renderer.ray_details.max_iterations));
+                ui.add(egui::DragValue::new(&mut renderer.core.ray_details.max_iterations));
                 ui.end_row(); 
+
+                if renderer.core.ray_details.integration_method.is_adaptive() {
+                    let details = &mut renderer.core.ray_details;
+
+                    ui.label("Tolerance");
+                    ui.add(egui::DragValue::new(&mut details.tolerance).speed(0.001).clamp_range(0.00001..=10.0));
+                    ui.end_row(); 
+
+                    ui.label("Error Scale");
+                    ui.add(egui::DragValue::new(&mut details.error_scale).speed(0.01).clamp_range(0.001..=100.0));
+                    ui.end_row(); 
+
+                    ui.label("Growth Threshold");
+                    ui.add(egui::DragValue::new(&mut details.growth_threshold).speed(0.000001).clamp_range(0.0..=1.0));
+                    ui.end_row(); 
+
+                    ui.label("Growth Factor");
+                    ui.add(egui::DragValue::new(&mut details.growth_factor).speed(0.0001).clamp_range(1.0..=2.0));
+                    ui.end_row(); 
+
+                    ui.label("Min Step");
+                    ui.add(egui::DragValue::new(&mut details.min_step).speed(0.0005).clamp_range(0.0001..=details.max_step));
+                    ui.end_row(); 
+
+                    ui.label("Max Step");
+                    ui.add(egui::DragValue::new(&mut details.max_step).speed(0.005).clamp_range(details.min_step..=100.0));
+                    ui.end_row(); 
+                }
             });
     }
 
@@ -85,11 +138,11 @@ impl RendererSettings {
                 ];
 
                 egui::ComboBox::from_id_source("edge_threshold_min")
-                    .selected_text(String::from(renderer.fxaa_details.edge_threshold_min))
+                    .selected_text(String::from(renderer.core.fxaa_details.edge_threshold_min))
                     .show_ui(ui, |ui| {
                         for option in options {
                             ui.selectable_value(
-                                &mut renderer.fxaa_details.edge_threshold_min,
+                                &mut renderer.core.fxaa_details.edge_threshold_min,
                                 option,
                                 String::from(option)
                             );
@@ -109,11 +162,11 @@ impl RendererSettings {
                 ];
 
                 egui::ComboBox::from_id_source("edge_threshold_max")
-                    .selected_text(String::from(renderer.fxaa_details.edge_threshold_max))
+                    .selected_text(String::from(renderer.core.fxaa_details.edge_threshold_max))
                     .show_ui(ui, |ui| {
                         for option in options {
                             ui.selectable_value(
-                                &mut renderer.fxaa_details.edge_threshold_max,
+                                &mut renderer.core.fxaa_details.edge_threshold_max,
                                 option,
                                 String::from(option)
                             );
@@ -161,23 +214,67 @@ impl RendererSettings {
 
 
                         ui.label("Division Threshold");
-                        ui.add(egui::DragValue::new(&mut renderer.ray_details.angle_division_threshold).speed(0.001).clamp_range(0.0..=PI*2.0));
+                        ui.add(egui::DragValue::new(&mut renderer.core.ray_details.angle_division_threshold).speed(0.001).clamp_range(0.0..=PI*2.0));
                         ui.end_row(); 
 
                         ui.label("Highlight Interpolation");
-                        let mut highlight_interpolation_bool = renderer.ray_details.highlight_interpolation != 0;
+                        let mut highlight_interpolation_bool = renderer.core.ray_details.highlight_interpolation != 0;
                         ui.checkbox(&mut highlight_interpolation_bool, "checked");
-                        renderer.ray_details.highlight_interpolation = highlight_interpolation_bool as i32; 
+                        renderer.core.ray_details.highlight_interpolation = highlight_interpolation_bool as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Lens Meshes");
+                        let mut lens_meshes_bool = renderer.core.ray_details.lens_meshes != 0;
+                        ui.checkbox(&mut lens_meshes_bool, "checked");
+                        renderer.core.ray_details.lens_meshes = lens_meshes_bool as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Deflection LUT");
+                        let mut deflection_lut_bool = renderer.core.ray_details.deflection_lut != 0;
+                        ui.checkbox(&mut deflection_lut_bool, "checked");
+                        renderer.core.ray_details.deflection_lut = deflection_lut_bool as i32; 
+                        ui.end_row(); 
+
+                        if renderer.core.ray_details.deflection_lut != 0 {
+                            ui.label("LUT Clearance");
+                            ui.add(egui::DragValue::new(&mut renderer.core.ray_details.deflection_clearance).speed(0.05).clamp_range(1.0..=20.0));
+                            ui.end_row(); 
+                        }
+
+                        ui.label("Filter Image Order");
+                        let mut order_filter_bool = renderer.core.ray_details.order_filter >= 0;
+                        ui.checkbox(&mut order_filter_bool, "checked");
+                        if order_filter_bool != (renderer.core.ray_details.order_filter >= 0) {
+                            renderer.core.ray_details.order_filter = if order_filter_bool { 0 } else { -1 };
+                        }
+                        ui.end_row(); 
+
+                        if renderer.core.ray_details.order_filter >= 0 {
+                            ui.label("Image Order");
+                            ui.add(egui::DragValue::new(&mut renderer.core.ray_details.order_filter).speed(0.05).clamp_range(0..=8));
+                            ui.end_row(); 
+                        }
+
+                        ui.label("Show Image Order");
+                        let mut show_image_order_bool = renderer.core.ray_details.show_image_order != 0;
+                        ui.checkbox(&mut show_image_order_bool, "checked");
+                        renderer.core.ray_details.show_image_order = show_image_order_bool as i32; 
+                        ui.end_row(); 
+
+                        ui.label("Sky Gravitational Shift");
+                        let mut sky_redshift_bool = renderer.core.ray_details.sky_redshift != 0;
+                        ui.checkbox(&mut sky_redshift_bool, "checked");
+                        renderer.core.ray_details.sky_redshift = sky_redshift_bool as i32; 
                         ui.end_row(); 
 
                         ui.label("Step Mode");
-                        ui.checkbox(&mut renderer.step_mode, "checked");
+                        ui.checkbox(&mut renderer.core.step_mode, "checked");
                         ui.end_row(); 
 
-                        if renderer.step_mode {
+                        if renderer.core.step_mode {
                             ui.label("Step");
                             if ui.button("Step").clicked() {
-                                renderer.step = true;
+                                renderer.core.step = true;
                             }
                             ui.end_row(); 
                         }
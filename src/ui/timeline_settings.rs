@@ -0,0 +1,134 @@
+use crate::scene::Scene;
+
+pub struct TimelineSettings {
+    visible: bool,
+}
+
+impl TimelineSettings {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene) {
+        egui::Window::new("Timeline")
+            .open(&mut self.visible)
+            .frame(egui::Frame::window(&egui::Style::default()))
+            .show(ctx, |ui| {
+                let timeline = &mut scene.timeline;
+
+                ui.horizontal(|ui| {
+                    let play_text = if timeline.playing { "Pause" } else { "Play" };
+
+                    if ui.add_enabled(!timeline.is_empty(), egui::Button::new(play_text)).clicked() {
+                        // play again from the start once stopped at the end
+                        if !timeline.playing && timeline.time >= timeline.duration() {
+                            timeline.set_time(0.0);
+                        }
+
+                        timeline.playing = !timeline.playing;
+                    }
+
+                    if ui.button("Rewind").clicked() {
+                        timeline.set_time(0.0);
+                    }
+
+                    ui.checkbox(&mut timeline.looping, "Loop");
+                });
+
+                egui::Grid::new("timeline_settings_grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Time:");
+
+                        // scrubbing can go past the end so new keyframes can be added there
+                        let mut time = timeline.time;
+                        if ui.add(egui::DragValue::new(&mut time).speed(0.01).clamp_range(0.0..=f32::MAX).suffix(" s")).changed() {
+                            timeline.time = time;
+                            timeline.scrubbed = true;
+                            timeline.playing = false;
+                        }
+                        ui.end_row();
+
+                        ui.label("Duration:");
+                        ui.label(format!("{:.2} s", timeline.duration()));
+                        ui.end_row();
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Key Camera").clicked() {
+                        timeline.key_camera(&scene.camera);
+                    }
+
+                    if ui.button("Key Black Holes").clicked() {
+                        timeline.key_black_holes(&scene.black_holes);
+                    }
+                });
+
+                ui.separator();
+
+                let timeline = &mut scene.timeline;
+                let mut go_to = None;
+                let mut remove_camera = None;
+                let mut remove_black_holes = None;
+
+                ui.collapsing("Camera Keyframes", |ui| {
+                    for (i, keyframe) in timeline.camera_keyframes.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{:.2} s", keyframe.time));
+
+                            if ui.button("Go").clicked() {
+                                go_to = Some(keyframe.time);
+                            }
+
+                            if ui.button("Remove").clicked() {
+                                remove_camera = Some(i);
+                            }
+                        });
+                    }
+                });
+
+                ui.collapsing("Black Hole Keyframes", |ui| {
+                    for (i, keyframe) in timeline.black_hole_keyframes.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{:.2} s", keyframe.time));
+
+                            if ui.button("Go").clicked() {
+                                go_to = Some(keyframe.time);
+                            }
+
+                            if ui.button("Remove").clicked() {
+                                remove_black_holes = Some(i);
+                            }
+                        });
+                    }
+                });
+
+                if let Some(i) = remove_camera {
+                    timeline.camera_keyframes.remove(i);
+                }
+
+                if let Some(i) = remove_black_holes {
+                    timeline.black_hole_keyframes.remove(i);
+                }
+
+                if let Some(time) = go_to {
+                    timeline.playing = false;
+                    timeline.set_time(time);
+                }
+            });
+    }
+
+    pub fn show(&mut self) {
+        self.visible = true;
+    }
+}
+
+impl Default for TimelineSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,88 @@
+use cgmath::Vector3;
+
+use crate::scene::{wormhole::{Wormhole, MAX_WORMHOLES}, Scene};
+
+pub struct WormholeSettings {
+    visible: bool,
+    selected: usize,
+}
+
+impl WormholeSettings {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            selected: 0,
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene) {
+        egui::Window::new("Wormhole Settings")
+            .open(&mut self.visible)
+            .frame(egui::Frame::window(&egui::Style::default()))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for i in 0..scene.wormholes.size() {
+                        ui.selectable_value(&mut self.selected, i, format!("Wormhole {}", i));
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let can_add = scene.wormholes.size() < MAX_WORMHOLES;
+
+                    if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                        let mut wormhole = Wormhole::new();
+                        wormhole.position = Vector3::new(40.0 * (scene.wormholes.size() + 1) as f32, 0.0, 20.0);
+
+                        scene.wormholes.insert(wormhole);
+                        self.selected = scene.wormholes.size() - 1;
+                    }
+
+                    let can_remove = scene.wormholes.size() > 0;
+
+                    if ui.add_enabled(can_remove, egui::Button::new("Remove")).clicked() {
+                        scene.wormholes.remove(self.selected);
+                    }
+                });
+
+                self.selected = self.selected.min(scene.wormholes.size().saturating_sub(1));
+
+                let Some(wormhole) = scene.wormholes.get_mut(self.selected) else {
+                    return;
+                };
+
+                ui.separator();
+
+                egui::Grid::new("wormhole_settings_grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Position:");
+                        ui.columns(3, |ui| {
+                            ui[0].add(egui::DragValue::new(&mut wormhole.position.x).speed(0.01));
+                            ui[1].add(egui::DragValue::new(&mut wormhole.position.y).speed(0.01));
+                            ui[2].add(egui::DragValue::new(&mut wormhole.position.z).speed(0.01));
+                        });
+                        ui.end_row();
+
+                        ui.label("Throat Radius:");
+                        ui.add(egui::DragValue::new(&mut wormhole.throat_radius).clamp_range(0.01..=100.0).speed(0.01));
+                        ui.end_row();
+
+                        ui.label("Relativity Radius:");
+                        ui.add(egui::DragValue::new(&mut wormhole.relativity_sphere_radius).clamp_range(wormhole.throat_radius..=1000.0).speed(0.05));
+                        ui.end_row();
+                    });
+            });
+    }
+
+    pub fn show(&mut self) {
+        self.visible = true;
+    }
+}
+
+impl Default for WormholeSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}